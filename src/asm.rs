@@ -0,0 +1,1111 @@
+//! Assembler turning CHIP-8 source into a program image.
+//!
+//! The syntax is the one the disassembler prints, so its listings can be reassembled once the
+//! address and byte columns are removed:
+//!
+//! ```text
+//! ; Comments run to the end of the line.
+//! SPEED = 3                   ; constants
+//! start:                      ; labels
+//!     LD V0, SPEED * 2 + 1    ; expressions: + - * / % & | ^ << >> ~, parentheses
+//!     LD I, sprite
+//!     DRW V0, V0, sprite_end - sprite
+//!     JP $                    ; $ is the address of the current line
+//! sprite:
+//!     db 0b11110000, 0x90, 0x90, 0xF0
+//!     dw 0x1234
+//! sprite_end:
+//! include "more.asm"          ; paths are relative to the including file
+//! ```
+//!
+//! Mnemonics, registers and keywords are case-insensitive; numbers are decimal, or hexadecimal
+//! and binary with `0x` and `0b`. Every instruction [`Instruction`] can decode is supported.
+
+use std::{collections::HashMap, error::Error, fmt, fs, path::Path, rc::Rc};
+
+use crate::{instruction::Instruction, machine::PROGRAM_START};
+
+/// Deepest nesting of `include` directives, which also stops an included file from including
+/// itself forever.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// An error in the source, with the place it was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub file: String,
+    /// Line number, starting at 1.
+    pub line: usize,
+    /// Column number, starting at 1.
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.file, self.line, self.column, self.message
+        )
+    }
+}
+
+impl Error for AsmError {}
+
+/// Assemble `source`. Included files are looked up relative to the current directory.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut assembler = Assembler::default();
+    assembler.parse_source(source, Rc::from("<input>"), Path::new(""), 0)?;
+    assembler.emit()
+}
+
+/// Assemble the file at `path`.
+pub fn assemble_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, AsmError> {
+    let path = path.as_ref();
+    let location = Location {
+        file: Rc::from(path.display().to_string()),
+        line: 0,
+        column: 0,
+    };
+
+    let mut assembler = Assembler::default();
+    assembler.parse_file(path, &location, 0)?;
+    assembler.emit()
+}
+
+#[derive(Debug, Clone)]
+struct Location {
+    file: Rc<str>,
+    line: usize,
+    column: usize,
+}
+
+impl Location {
+    fn at(&self, column: usize) -> Location {
+        Location {
+            column,
+            ..self.clone()
+        }
+    }
+
+    fn error<T, M: Into<String>>(&self, message: M) -> Result<T, AsmError> {
+        Err(AsmError {
+            file: self.file.to_string(),
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        })
+    }
+}
+
+/// An expression, kept as text until every label is known.
+#[derive(Debug, Clone)]
+struct Expr {
+    text: String,
+    location: Location,
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    Register(usize),
+    /// `Vx - Vy`
+    Range(usize, usize),
+    /// One of `I`, `[I]`, `DT`, `ST`, `K`, `F`, `HF`, `B` and `R`, upper case.
+    Keyword(&'static str),
+    /// `LONG expr`
+    Long(Expr),
+    Expr(Expr),
+}
+
+impl Operand {
+    fn location(&self) -> Option<&Location> {
+        match self {
+            Operand::Long(expr) | Operand::Expr(expr) => Some(&expr.location),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Data {
+    Byte(Expr),
+    String(Vec<u8>),
+}
+
+#[derive(Debug)]
+enum Statement {
+    Instruction {
+        mnemonic: String,
+        operands: Vec<Operand>,
+        location: Location,
+    },
+    Bytes(Vec<Data>),
+    Words(Vec<Expr>),
+}
+
+#[derive(Default)]
+struct Assembler {
+    /// Statements with the address they are assembled at.
+    statements: Vec<(usize, Statement)>,
+    labels: HashMap<String, usize>,
+    constants: HashMap<String, Expr>,
+    address: usize,
+}
+
+impl Assembler {
+    fn parse_file(&mut self, path: &Path, from: &Location, depth: usize) -> Result<(), AsmError> {
+        if depth > MAX_INCLUDE_DEPTH {
+            return from.error("includes nested too deeply");
+        }
+
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) => return from.error(format!("cannot read {}: {}", path.display(), err)),
+        };
+
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let file = Rc::from(path.display().to_string());
+        self.parse_source(&source, file, directory, depth)
+    }
+
+    fn parse_source(
+        &mut self,
+        source: &str,
+        file: Rc<str>,
+        directory: &Path,
+        depth: usize,
+    ) -> Result<(), AsmError> {
+        if self.address == 0 {
+            self.address = PROGRAM_START;
+        }
+
+        for (index, line) in source.lines().enumerate() {
+            let location = Location {
+                file: file.clone(),
+                line: index + 1,
+                column: 1,
+            };
+
+            self.parse_line(line, &location, directory, depth)?;
+        }
+
+        Ok(())
+    }
+
+    fn parse_line(
+        &mut self,
+        line: &str,
+        location: &Location,
+        directory: &Path,
+        depth: usize,
+    ) -> Result<(), AsmError> {
+        let mut cursor = Cursor::new(strip_comment(line));
+        cursor.skip_space();
+
+        // A label may come first, followed by anything else on the same line.
+        let checkpoint = cursor.position;
+        if let Some(name) = cursor.identifier() {
+            if cursor.eat(':') {
+                self.define_label(name, location.at(checkpoint + 1))?;
+                cursor.skip_space();
+            } else {
+                cursor.position = checkpoint;
+            }
+        }
+
+        let start = cursor.position;
+        let Some(word) = cursor.identifier() else {
+            return if cursor.at_end() {
+                Ok(())
+            } else {
+                location
+                    .at(start + 1)
+                    .error("expected a label, directive or instruction")
+            };
+        };
+
+        cursor.skip_space();
+        let rest_column = cursor.position;
+        let rest = cursor.rest().trim_end();
+
+        if let Some(value) = rest.strip_prefix('=') {
+            let column = rest_column + 2 + (value.len() - value.trim_start().len());
+            return self.define_constant(word, value.trim(), location.at(start + 1), column);
+        }
+
+        match word.to_ascii_lowercase().as_str() {
+            "equ" => location
+                .at(start + 1)
+                .error("EQU needs a name before it, as in NAME EQU value"),
+            "db" => {
+                let items = split_operands(rest, rest_column, location)?;
+                let mut data = Vec::new();
+                let mut size = 0;
+
+                for expr in items {
+                    match parse_string(&expr)? {
+                        Some(bytes) => {
+                            size += bytes.len();
+                            data.push(Data::String(bytes))
+                        }
+                        None => {
+                            size += 1;
+                            data.push(Data::Byte(expr))
+                        }
+                    }
+                }
+
+                self.push(size, Statement::Bytes(data));
+                Ok(())
+            }
+            "dw" => {
+                let items = split_operands(rest, rest_column, location)?;
+                self.push(items.len() * 2, Statement::Words(items));
+                Ok(())
+            }
+            "include" => {
+                let expr = Expr {
+                    text: rest.to_string(),
+                    location: location.at(rest_column + 1),
+                };
+
+                let Some(name) = parse_string(&expr)? else {
+                    return expr.location.error("expected a quoted file name");
+                };
+
+                let path = directory.join(String::from_utf8_lossy(&name).as_ref());
+                self.parse_file(&path, &expr.location, depth + 1)
+            }
+            _ => {
+                // NAME EQU value
+                let mut lookahead = Cursor::new(rest);
+                if let Some(keyword) = lookahead.identifier() {
+                    if keyword.eq_ignore_ascii_case("equ") {
+                        lookahead.skip_space();
+                        let column = rest_column + lookahead.position + 1;
+                        let value = lookahead.rest().trim();
+                        return self.define_constant(word, value, location.at(start + 1), column);
+                    }
+                }
+
+                let operands = split_operands(rest, rest_column, location)?
+                    .into_iter()
+                    .map(classify)
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let long = matches!(operands.get(1), Some(Operand::Long(_)));
+                let statement = Statement::Instruction {
+                    mnemonic: word.to_ascii_uppercase(),
+                    operands,
+                    location: location.at(start + 1),
+                };
+
+                self.push(if long { 4 } else { 2 }, statement);
+                Ok(())
+            }
+        }
+    }
+
+    fn push(&mut self, size: usize, statement: Statement) {
+        self.statements.push((self.address, statement));
+        self.address += size;
+    }
+
+    fn define_label(&mut self, name: &str, location: Location) -> Result<(), AsmError> {
+        self.check_name(name, &location)?;
+        self.labels.insert(name.to_string(), self.address);
+        Ok(())
+    }
+
+    fn define_constant(
+        &mut self,
+        name: &str,
+        value: &str,
+        location: Location,
+        column: usize,
+    ) -> Result<(), AsmError> {
+        self.check_name(name, &location)?;
+
+        if value.is_empty() {
+            return location.at(column).error("expected a value");
+        }
+
+        let expr = Expr {
+            text: value.to_string(),
+            location: location.at(column),
+        };
+
+        self.constants.insert(name.to_string(), expr);
+        Ok(())
+    }
+
+    fn check_name(&self, name: &str, location: &Location) -> Result<(), AsmError> {
+        if register(name).is_some() || keyword(name).is_some() {
+            location.error(format!("{} is reserved", name))
+        } else if self.labels.contains_key(name) || self.constants.contains_key(name) {
+            location.error(format!("{} is already defined", name))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn emit(&self) -> Result<Vec<u8>, AsmError> {
+        let mut image = Vec::new();
+
+        for (address, statement) in &self.statements {
+            let evaluator = Evaluator {
+                assembler: self,
+                address: *address,
+            };
+
+            match statement {
+                Statement::Instruction {
+                    mnemonic,
+                    operands,
+                    location,
+                } => {
+                    let instruction = evaluator.instruction(mnemonic, operands, location)?;
+                    let (opcode, extra) = instruction.encode();
+                    image.extend_from_slice(&opcode.to_be_bytes());
+
+                    if let Some(extra) = extra {
+                        image.extend_from_slice(&extra.to_be_bytes());
+                    }
+                }
+                Statement::Bytes(data) => {
+                    for item in data {
+                        match item {
+                            Data::String(bytes) => image.extend_from_slice(bytes),
+                            Data::Byte(expr) => image.push(evaluator.byte(expr)?),
+                        }
+                    }
+                }
+                Statement::Words(exprs) => {
+                    for expr in exprs {
+                        let word = evaluator.ranged(expr, -0x8000, 0xFFFF, "word")? as u16;
+                        image.extend_from_slice(&word.to_be_bytes())
+                    }
+                }
+            }
+        }
+
+        Ok(image)
+    }
+}
+
+/// Evaluates the operands of the statement at `address`.
+struct Evaluator<'a> {
+    assembler: &'a Assembler,
+    address: usize,
+}
+
+impl Evaluator<'_> {
+    fn evaluate(&self, expr: &Expr) -> Result<i64, AsmError> {
+        self.evaluate_nested(expr, &mut Vec::new())
+    }
+
+    fn evaluate_nested(&self, expr: &Expr, visiting: &mut Vec<String>) -> Result<i64, AsmError> {
+        let mut parser = ExprParser {
+            cursor: Cursor::new(&expr.text),
+            location: &expr.location,
+            evaluator: self,
+            visiting,
+        };
+
+        let value = parser.parse(0)?;
+        parser.cursor.skip_space();
+
+        if !parser.cursor.at_end() {
+            return parser.error("unexpected character in expression");
+        }
+
+        Ok(value)
+    }
+
+    fn symbol(
+        &self,
+        name: &str,
+        location: &Location,
+        visiting: &mut Vec<String>,
+    ) -> Result<i64, AsmError> {
+        if let Some(&address) = self.assembler.labels.get(name) {
+            return Ok(address as i64);
+        }
+
+        let Some(expr) = self.assembler.constants.get(name) else {
+            return if register(name).is_some() || keyword(name).is_some() {
+                location.error(format!("{} cannot be used in an expression", name))
+            } else {
+                location.error(format!("{} is not defined", name))
+            };
+        };
+
+        if visiting.iter().any(|visited| visited == name) {
+            return location.error(format!("{} is defined in terms of itself", name));
+        }
+
+        visiting.push(name.to_string());
+        let value = self.evaluate_nested(expr, visiting);
+        visiting.pop();
+        value
+    }
+
+    fn ranged(&self, expr: &Expr, min: i64, max: i64, what: &str) -> Result<i64, AsmError> {
+        let value = self.evaluate(expr)?;
+
+        if value < min || value > max {
+            expr.location
+                .error(format!("{} does not fit in a {}", value, what))
+        } else {
+            Ok(value)
+        }
+    }
+
+    fn byte(&self, expr: &Expr) -> Result<u8, AsmError> {
+        Ok(self.ranged(expr, -0x80, 0xFF, "byte")? as u8)
+    }
+
+    fn nibble(&self, expr: &Expr) -> Result<u8, AsmError> {
+        Ok(self.ranged(expr, 0, 0xF, "nibble")? as u8)
+    }
+
+    fn address(&self, expr: &Expr) -> Result<u16, AsmError> {
+        Ok(self.ranged(expr, 0, 0xFFF, "12-bit address")? as u16)
+    }
+
+    fn long_address(&self, expr: &Expr) -> Result<u16, AsmError> {
+        Ok(self.ranged(expr, 0, 0xFFFF, "16-bit address")? as u16)
+    }
+
+    fn instruction(
+        &self,
+        mnemonic: &str,
+        operands: &[Operand],
+        location: &Location,
+    ) -> Result<Instruction, AsmError> {
+        use Instruction::*;
+        use Operand::{Expr as E, Keyword as K, Long, Range, Register as V};
+
+        let instruction = match (mnemonic, operands) {
+            ("SYS", [E(a)]) => Sys(self.address(a)?),
+            ("SCD", [E(n)]) => ScrollDown(self.nibble(n)?),
+            ("CLS", []) => Clear,
+            ("RET", []) => Return,
+            ("SCR", []) => ScrollRight,
+            ("SCL", []) => ScrollLeft,
+            ("EXIT", []) => Exit,
+            ("LOW", []) => Low,
+            ("HIGH", []) => High,
+            ("JP", [E(a)]) => Jump(self.address(a)?),
+            ("JP", [V(0), E(a)]) => JumpOffset(self.address(a)?),
+            ("CALL", [E(a)]) => Call(self.address(a)?),
+            ("SE", [V(x), V(y)]) => SkipEqual(*x, *y),
+            ("SE", [V(x), E(kk)]) => SkipEqualByte(*x, self.byte(kk)?),
+            ("SNE", [V(x), V(y)]) => SkipNotEqual(*x, *y),
+            ("SNE", [V(x), E(kk)]) => SkipNotEqualByte(*x, self.byte(kk)?),
+            ("LD", [K("[I]"), Range(x, y)]) => StoreRange(*x, *y),
+            ("LD", [Range(x, y), K("[I]")]) => LoadRange(*x, *y),
+            ("LD", [V(x), V(y)]) => Load(*x, *y),
+            ("LD", [V(x), E(kk)]) => LoadByte(*x, self.byte(kk)?),
+            ("LD", [K("I"), E(a)]) => LoadI(self.address(a)?),
+            ("LD", [K("I"), Long(a)]) => LoadLongI(self.long_address(a)?),
+            ("LD", [V(x), K("DT")]) => LoadDelay(*x),
+            ("LD", [V(x), K("K")]) => WaitKey(*x),
+            ("LD", [K("DT"), V(x)]) => SetDelay(*x),
+            ("LD", [K("ST"), V(x)]) => SetSound(*x),
+            ("LD", [K("F"), V(x)]) => Font(*x),
+            ("LD", [K("HF"), V(x)]) => BigFont(*x),
+            ("LD", [K("B"), V(x)]) => Bcd(*x),
+            ("LD", [K("[I]"), V(x)]) => Store(*x),
+            ("LD", [V(x), K("[I]")]) => Restore(*x),
+            ("LD", [K("R"), V(x)]) => StoreFlags(*x),
+            ("LD", [V(x), K("R")]) => RestoreFlags(*x),
+            ("ADD", [V(x), V(y)]) => Add(*x, *y),
+            ("ADD", [V(x), E(kk)]) => AddByte(*x, self.byte(kk)?),
+            ("ADD", [K("I"), V(x)]) => AddI(*x),
+            ("OR", [V(x), V(y)]) => Or(*x, *y),
+            ("AND", [V(x), V(y)]) => And(*x, *y),
+            ("XOR", [V(x), V(y)]) => Xor(*x, *y),
+            ("SUB", [V(x), V(y)]) => Sub(*x, *y),
+            ("SUBN", [V(x), V(y)]) => SubNegated(*x, *y),
+            ("SHR", [V(x)]) => ShiftRight(*x, *x),
+            ("SHR", [V(x), V(y)]) => ShiftRight(*x, *y),
+            ("SHL", [V(x)]) => ShiftLeft(*x, *x),
+            ("SHL", [V(x), V(y)]) => ShiftLeft(*x, *y),
+            ("RND", [V(x), E(kk)]) => Random(*x, self.byte(kk)?),
+            ("DRW", [V(x), V(y), E(n)]) => Draw(*x, *y, self.nibble(n)?),
+            ("SKP", [V(x)]) => SkipPressed(*x),
+            ("SKNP", [V(x)]) => SkipNotPressed(*x),
+            ("PLANE", [E(n)]) => Plane(self.nibble(n)?),
+            ("AUDIO", []) => Audio,
+            ("PITCH", [V(x)]) => Pitch(*x),
+            _ => {
+                let known = [
+                    "SYS", "SCD", "CLS", "RET", "SCR", "SCL", "EXIT", "LOW", "HIGH", "JP", "CALL",
+                    "SE", "SNE", "LD", "ADD", "OR", "AND", "XOR", "SUB", "SUBN", "SHR", "SHL",
+                    "RND", "DRW", "SKP", "SKNP", "PLANE", "AUDIO", "PITCH",
+                ];
+
+                return if known.contains(&mnemonic) {
+                    let location = operands
+                        .iter()
+                        .find_map(Operand::location)
+                        .unwrap_or(location);
+                    location.error(format!("invalid operands for {}", mnemonic))
+                } else {
+                    location.error(format!("unknown instruction {}", mnemonic))
+                };
+            }
+        };
+
+        Ok(instruction)
+    }
+}
+
+/// Precedence climbing parser evaluating an expression as it goes.
+struct ExprParser<'a> {
+    cursor: Cursor<'a>,
+    location: &'a Location,
+    evaluator: &'a Evaluator<'a>,
+    /// Constants being evaluated, to catch definitions that refer to themselves.
+    visiting: &'a mut Vec<String>,
+}
+
+impl ExprParser<'_> {
+    const BINARY: [(&'static str, u8); 11] = [
+        ("|", 1),
+        ("^", 2),
+        ("&", 3),
+        ("<<", 4),
+        (">>", 4),
+        ("+", 5),
+        ("-", 5),
+        ("*", 6),
+        ("/", 6),
+        ("%", 6),
+        ("", 0),
+    ];
+
+    fn error<T, M: Into<String>>(&self, message: M) -> Result<T, AsmError> {
+        self.location
+            .at(self.location.column + self.cursor.position)
+            .error(message)
+    }
+
+    fn parse(&mut self, min_precedence: u8) -> Result<i64, AsmError> {
+        let mut value = self.unary()?;
+
+        loop {
+            self.cursor.skip_space();
+            let rest = self.cursor.rest();
+
+            let Some(&(operator, precedence)) = Self::BINARY
+                .iter()
+                .find(|(operator, _)| !operator.is_empty() && rest.starts_with(operator))
+            else {
+                return Ok(value);
+            };
+
+            if precedence <= min_precedence {
+                return Ok(value);
+            }
+
+            let column = self.cursor.position;
+            self.cursor.position += operator.len();
+            let rhs = self.parse(precedence)?;
+
+            value = match operator {
+                "|" => value | rhs,
+                "^" => value ^ rhs,
+                "&" => value & rhs,
+                "<<" => value.checked_shl(rhs as u32).unwrap_or(0),
+                ">>" => value.checked_shr(rhs as u32).unwrap_or(0),
+                "+" => value.wrapping_add(rhs),
+                "-" => value.wrapping_sub(rhs),
+                "*" => value.wrapping_mul(rhs),
+                _ => {
+                    let result = match operator {
+                        "/" => value.checked_div(rhs),
+                        _ => value.checked_rem(rhs),
+                    };
+
+                    let Some(result) = result else {
+                        self.cursor.position = column;
+                        return self.error(if rhs == 0 {
+                            "division by zero"
+                        } else {
+                            "division overflows"
+                        });
+                    };
+
+                    result
+                }
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<i64, AsmError> {
+        self.cursor.skip_space();
+
+        if self.cursor.eat('-') {
+            Ok(self.unary()?.wrapping_neg())
+        } else if self.cursor.eat('~') {
+            Ok(!self.unary()?)
+        } else if self.cursor.eat('+') {
+            self.unary()
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<i64, AsmError> {
+        let start = self.cursor.position;
+
+        if self.cursor.eat('(') {
+            let value = self.parse(0)?;
+            self.cursor.skip_space();
+
+            if !self.cursor.eat(')') {
+                return self.error("expected )");
+            }
+
+            return Ok(value);
+        }
+
+        if self.cursor.eat('$') {
+            return Ok(self.evaluator.address as i64);
+        }
+
+        if let Some(name) = self.cursor.identifier() {
+            let location = self.location.at(self.location.column + start);
+            return self.evaluator.symbol(name, &location, self.visiting);
+        }
+
+        let digits = self.cursor.take_while(|c| c.is_ascii_alphanumeric());
+
+        let parsed = if let Some(hex) = digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+            i64::from_str_radix(hex, 16)
+        } else if let Some(bin) = digits.strip_prefix("0b").or(digits.strip_prefix("0B")) {
+            i64::from_str_radix(bin, 2)
+        } else {
+            digits.parse()
+        };
+
+        match parsed {
+            Ok(value) => Ok(value),
+            Err(_) => {
+                self.cursor.position = start;
+
+                if digits.is_empty() {
+                    self.error("expected a value")
+                } else {
+                    self.error(format!("invalid number {}", digits))
+                }
+            }
+        }
+    }
+}
+
+/// A position in a line of source.
+struct Cursor<'a> {
+    text: &'a str,
+    /// Byte offset from the start of `text`.
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(text: &'a str) -> Self {
+        Cursor { text, position: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn at_end(&self) -> bool {
+        self.rest().is_empty()
+    }
+
+    fn skip_space(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.rest().starts_with(c) {
+            self.position += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, predicate: F) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(|c| !predicate(c)).unwrap_or(rest.len());
+        self.position += len;
+        &rest[..len]
+    }
+
+    fn identifier(&mut self) -> Option<&'a str> {
+        let rest = self.rest();
+
+        if !rest.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '.') {
+            return None;
+        }
+
+        Some(self.take_while(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+    }
+}
+
+/// Cut `line` at its first `;` outside of a string.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => return &line[..index],
+            _ => {}
+        }
+    }
+
+    line
+}
+
+/// Split `text`, starting at byte `offset` of the line, at the commas outside of parentheses
+/// and strings.
+fn split_operands(text: &str, offset: usize, location: &Location) -> Result<Vec<Expr>, AsmError> {
+    let mut operands = Vec::new();
+
+    if text.trim().is_empty() {
+        return Ok(operands);
+    }
+
+    let mut depth = 0;
+    let mut quoted = false;
+    let mut start = 0;
+
+    for (index, c) in text.char_indices().chain([(text.len(), ',')]) {
+        match c {
+            '"' => quoted = !quoted,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted => depth -= 1,
+            ',' if !quoted && depth == 0 => {
+                let operand = &text[start..index];
+                let column = offset + start + (operand.len() - operand.trim_start().len()) + 1;
+
+                if operand.trim().is_empty() {
+                    return location.at(column).error("expected an operand");
+                }
+
+                operands.push(Expr {
+                    text: operand.trim().to_string(),
+                    location: location.at(column),
+                });
+
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+
+    if quoted {
+        location.at(offset + 1).error("unterminated string")
+    } else if depth != 0 {
+        location.at(offset + 1).error("unbalanced parentheses")
+    } else {
+        Ok(operands)
+    }
+}
+
+/// The bytes of a `"quoted string"`, or `None` if `expr` isn't one.
+fn parse_string(expr: &Expr) -> Result<Option<Vec<u8>>, AsmError> {
+    let Some(inner) = expr.text.strip_prefix('"') else {
+        return Ok(None);
+    };
+
+    match inner.strip_suffix('"') {
+        Some(inner) if !inner.contains('"') => Ok(Some(inner.as_bytes().to_vec())),
+        _ => expr.location.error("unterminated string"),
+    }
+}
+
+fn register(text: &str) -> Option<usize> {
+    let digit = text.strip_prefix(['V', 'v'])?;
+
+    if digit.len() == 1 {
+        usize::from_str_radix(digit, 16).ok()
+    } else {
+        None
+    }
+}
+
+fn keyword(text: &str) -> Option<&'static str> {
+    ["I", "[I]", "DT", "ST", "K", "F", "HF", "B", "R"]
+        .into_iter()
+        .find(|keyword| keyword.eq_ignore_ascii_case(text))
+}
+
+fn classify(expr: Expr) -> Result<Operand, AsmError> {
+    let text = expr.text.as_str();
+
+    if let Some(x) = register(text) {
+        return Ok(Operand::Register(x));
+    }
+
+    if let Some(keyword) = keyword(&text.replace(' ', "")) {
+        return Ok(Operand::Keyword(keyword));
+    }
+
+    if let Some((first, last)) = text.split_once('-') {
+        if let (Some(x), Some(y)) = (register(first.trim()), register(last.trim())) {
+            return Ok(Operand::Range(x, y));
+        }
+    }
+
+    let mut words = text.splitn(2, char::is_whitespace);
+    if let (Some(long), Some(value)) = (words.next(), words.next()) {
+        if long.eq_ignore_ascii_case("long") {
+            let column = expr.location.column + text.len() - value.trim_start().len();
+
+            return Ok(Operand::Long(Expr {
+                text: value.trim().to_string(),
+                location: expr.location.at(column),
+            }));
+        }
+    }
+
+    Ok(Operand::Expr(expr))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+    use crate::platform::Platform;
+
+    fn assemble_ok(source: &str) -> Vec<u8> {
+        assemble(source).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// The line, column and message of the error assembling `source`.
+    fn error(source: &str) -> (usize, usize, String) {
+        let err = assemble(source).unwrap_err();
+        (err.line, err.column, err.message)
+    }
+
+    #[test]
+    fn instruction_forms() {
+        let forms: [(&str, &[u8]); 51] = [
+            ("SYS 0x123", &[0x01, 0x23]),
+            ("SCD 5", &[0x00, 0xC5]),
+            ("CLS", &[0x00, 0xE0]),
+            ("RET", &[0x00, 0xEE]),
+            ("SCR", &[0x00, 0xFB]),
+            ("SCL", &[0x00, 0xFC]),
+            ("EXIT", &[0x00, 0xFD]),
+            ("LOW", &[0x00, 0xFE]),
+            ("HIGH", &[0x00, 0xFF]),
+            ("JP 0x345", &[0x13, 0x45]),
+            ("JP V0, 0x345", &[0xB3, 0x45]),
+            ("CALL 0x345", &[0x23, 0x45]),
+            ("SE V1, V2", &[0x51, 0x20]),
+            ("SE V1, 0x22", &[0x31, 0x22]),
+            ("SNE V1, V2", &[0x91, 0x20]),
+            ("SNE V1, 0x22", &[0x41, 0x22]),
+            ("LD [I], V1 - V4", &[0x51, 0x42]),
+            ("LD V1 - V4, [I]", &[0x51, 0x43]),
+            ("LD V1, V2", &[0x81, 0x20]),
+            ("LD V1, 0x22", &[0x61, 0x22]),
+            ("LD I, 0x345", &[0xA3, 0x45]),
+            ("LD I, LONG 0x1234", &[0xF0, 0x00, 0x12, 0x34]),
+            ("LD V1, DT", &[0xF1, 0x07]),
+            ("LD V1, K", &[0xF1, 0x0A]),
+            ("LD DT, V1", &[0xF1, 0x15]),
+            ("LD ST, V1", &[0xF1, 0x18]),
+            ("LD F, V1", &[0xF1, 0x29]),
+            ("LD HF, V1", &[0xF1, 0x30]),
+            ("LD B, V1", &[0xF1, 0x33]),
+            ("LD [I], V1", &[0xF1, 0x55]),
+            ("LD V1, [I]", &[0xF1, 0x65]),
+            ("LD R, V1", &[0xF1, 0x75]),
+            ("LD V1, R", &[0xF1, 0x85]),
+            ("ADD V1, V2", &[0x81, 0x24]),
+            ("ADD V1, 0x22", &[0x71, 0x22]),
+            ("ADD I, V1", &[0xF1, 0x1E]),
+            ("OR V1, V2", &[0x81, 0x21]),
+            ("AND V1, V2", &[0x81, 0x22]),
+            ("XOR V1, V2", &[0x81, 0x23]),
+            ("SUB V1, V2", &[0x81, 0x25]),
+            ("SUBN V1, V2", &[0x81, 0x27]),
+            ("SHR V1", &[0x81, 0x16]),
+            ("SHR V1, V2", &[0x81, 0x26]),
+            ("SHL V1", &[0x81, 0x1E]),
+            ("SHL V1, V2", &[0x81, 0x2E]),
+            ("RND V1, 0x22", &[0xC1, 0x22]),
+            ("DRW V1, V2, 5", &[0xD1, 0x25]),
+            ("SKP V1", &[0xE1, 0x9E]),
+            ("SKNP V1", &[0xE1, 0xA1]),
+            ("PLANE 3", &[0xF3, 0x01]),
+            ("AUDIO", &[0xF0, 0x02]),
+        ];
+
+        for (source, bytes) in forms {
+            assert_eq!(assemble_ok(source), bytes, "{}", source);
+        }
+
+        assert_eq!(assemble_ok("PITCH V1"), [0xF1, 0x3A]);
+        assert_eq!(assemble_ok("  drw va, vB, 0xf"), [0xDA, 0xBF]);
+    }
+
+    #[test]
+    fn labels_and_forward_references() {
+        let source = "\
+start:  JP end
+        CALL sub
+sub:    RET
+end:    JP start
+        JP $
+";
+        assert_eq!(
+            assemble_ok(source),
+            [0x12, 0x06, 0x22, 0x04, 0x00, 0xEE, 0x12, 0x00, 0x12, 0x08]
+        );
+    }
+
+    #[test]
+    fn constants_and_expressions() {
+        let source = "\
+SPEED = 3
+LIMIT EQU SPEED * 10 - 1
+        LD V0, SPEED * 2 + 1
+        LD V1, LIMIT
+        LD V2, (1 << 4) | 0b11 ^ 1
+        LD V3, -1
+        LD V4, ~0xF0 & 0xFF
+        LD V5, 17 % 5 + 9 / 2
+";
+        assert_eq!(
+            assemble_ok(source),
+            [0x60, 7, 0x61, 29, 0x62, 0x12, 0x63, 0xFF, 0x64, 0x0F, 0x65, 6]
+        );
+    }
+
+    #[test]
+    fn data() {
+        let source = "\
+        LD I, table
+table:  db 1, 0xFF, -128, \"Hi\"
+        dw 0x1234, table, -1
+";
+        assert_eq!(
+            assemble_ok(source),
+            [0xA2, 0x02, 1, 0xFF, 0x80, b'H', b'i', 0x12, 0x34, 0x02, 0x02, 0xFF, 0xFF]
+        );
+    }
+
+    #[test]
+    fn includes() {
+        let dir = env::temp_dir().join(format!("chip8-asm-{}", process::id()));
+        fs::create_dir_all(dir.join("lib")).unwrap();
+
+        fs::write(dir.join("main.asm"), "JP draw\ninclude \"lib/draw.asm\"\n").unwrap();
+        fs::write(
+            dir.join("lib").join("draw.asm"),
+            "draw: CLS\ninclude \"data.asm\"\n",
+        )
+        .unwrap();
+        fs::write(dir.join("lib").join("data.asm"), "db 7\n").unwrap();
+        fs::write(dir.join("loop.asm"), "CLS\ninclude \"loop.asm\"\n").unwrap();
+
+        let image = assemble_file(dir.join("main.asm"));
+        let looping = assemble_file(dir.join("loop.asm")).unwrap_err();
+        let missing = assemble("\n  include \"missing.asm\"").unwrap_err();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(image.unwrap(), [0x12, 0x02, 0x00, 0xE0, 7]);
+        assert_eq!(looping.message, "includes nested too deeply");
+        assert_eq!((missing.line, missing.column), (2, 11));
+        assert!(missing.message.starts_with("cannot read missing.asm"));
+    }
+
+    #[test]
+    fn error_positions() {
+        let message = |message: &str| message.to_string();
+
+        assert_eq!(
+            error("CLS\n  FOO V1"),
+            (2, 3, message("unknown instruction FOO"))
+        );
+        assert_eq!(
+            error("  LD V1, missing + 1"),
+            (1, 10, message("missing is not defined"))
+        );
+        assert_eq!(
+            error("LD V1, 0x100"),
+            (1, 8, message("256 does not fit in a byte"))
+        );
+        assert_eq!(
+            error("DRW V1, V2, 16"),
+            (1, 13, message("16 does not fit in a nibble"))
+        );
+        assert_eq!(
+            error("LD V1, 4 / (2 - 2)"),
+            (1, 10, message("division by zero"))
+        );
+        assert_eq!(error("LD V1, 4 % 0"), (1, 10, message("division by zero")));
+        assert_eq!(
+            error("LD V1, (-9223372036854775807-1)/-1"),
+            (1, 32, message("division overflows"))
+        );
+        assert_eq!(
+            error("LD V1, (-9223372036854775807-1) % -1"),
+            (1, 33, message("division overflows"))
+        );
+        assert_eq!(
+            error("LD V1, (1 + 2"),
+            (1, 4, message("unbalanced parentheses"))
+        );
+        assert_eq!(
+            error("ADD V1, I"),
+            (1, 1, message("invalid operands for ADD"))
+        );
+        assert_eq!(
+            error("JP 0x200, 1"),
+            (1, 4, message("invalid operands for JP"))
+        );
+        assert_eq!(
+            error("p = q\nq = p\nLD V0, p"),
+            (2, 5, message("p is defined in terms of itself"))
+        );
+        assert_eq!(
+            error("x: CLS\n x: CLS"),
+            (2, 2, message("x is already defined"))
+        );
+        assert_eq!(error("V3: CLS"), (1, 1, message("V3 is reserved")));
+        assert_eq!(error("db \"open"), (1, 4, message("unterminated string")));
+    }
+
+    /// Every opcode the shared decoder knows, printed as the disassembler prints it, assembles
+    /// back to the same bytes.
+    #[test]
+    fn disassembly_round_trip() {
+        for opcode in 0..=u16::MAX {
+            let Some(instruction) = Instruction::decode(opcode, 0x1234, Platform::XoChip) else {
+                continue;
+            };
+
+            let source = instruction.to_string();
+            let (opcode, extra) = instruction.encode();
+            let mut expected = opcode.to_be_bytes().to_vec();
+            expected.extend(extra.map(u16::to_be_bytes).into_iter().flatten());
+
+            assert_eq!(assemble_ok(&source), expected, "{}", source);
+            assert_eq!(
+                Instruction::decode(opcode, 0x1234, Platform::XoChip),
+                Some(instruction)
+            );
+        }
+    }
+}
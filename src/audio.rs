@@ -1,22 +1,194 @@
-use sdl2::Sdl;
+use crate::{
+    error::EmulatorError,
+    state::{Reader, Writer},
+};
 
+/// Shape of the buzzer tone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Sawtooth,
+    Sine,
+}
+
+impl Waveform {
+    /// Names accepted by [`Waveform::from_name`], with the waveform they select.
+    pub const NAMES: [(&'static str, Waveform); 4] = [
+        ("square", Waveform::Square),
+        ("triangle", Waveform::Triangle),
+        ("sawtooth", Waveform::Sawtooth),
+        ("sine", Waveform::Sine),
+    ];
+
+    /// Look up a waveform by name.
+    pub fn from_name(name: &str) -> Option<Waveform> {
+        Waveform::NAMES
+            .iter()
+            .find(|(waveform, _)| waveform.eq_ignore_ascii_case(name))
+            .map(|&(_, waveform)| waveform)
+    }
+
+    /// Value of the wave at `phase`, from 0 to 1, between -1 and 1.
+    pub fn sample(&self, phase: f32) -> f32 {
+        match self {
+            Waveform::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Sawtooth => 2.0 * phase - 1.0,
+            Waveform::Sine => (phase * std::f32::consts::TAU).sin(),
+        }
+    }
+}
+
+/// Sound of the buzzer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tone {
+    /// Frequency in Hz.
+    pub frequency: f32,
+    /// Volume from 0 to 1.
+    pub volume: f32,
+    pub waveform: Waveform,
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        Tone {
+            frequency: 440.0,
+            volume: 0.25,
+            waveform: Waveform::Square,
+        }
+    }
+}
+
+/// The delay and sound timers.
 pub struct Audio {
+    /// Delay timer, readable by programs through `Fx07`.
     pub delay: u8,
+    /// Sound timer; the buzzer sounds while it is non-zero.
     pub sound: u8,
+    /// XO-CHIP 1-bit audio pattern, 128 samples played most significant bit first, once the
+    /// program loaded one with `F002`.
+    pub pattern: Option<[u8; 16]>,
+    /// XO-CHIP playback pitch; the pattern plays at 4000 * 2^((pitch - 64) / 48) Hz.
+    pub pitch: u8,
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Audio {
-    pub fn new(_sdl: &Sdl) -> Self {
-        Audio { delay: 0, sound: 0 }
+    pub fn new() -> Self {
+        Audio {
+            delay: 0,
+            sound: 0,
+            pattern: None,
+            pitch: 64,
+        }
+    }
+
+    pub(crate) fn save(&self, writer: &mut Writer) {
+        writer.u8(self.delay);
+        writer.u8(self.sound);
+        writer.bool(self.pattern.is_some());
+        writer.bytes(&self.pattern.unwrap_or_default());
+        writer.u8(self.pitch);
     }
 
+    pub(crate) fn load(reader: &mut Reader) -> Result<Self, EmulatorError> {
+        let delay = reader.u8()?;
+        let sound = reader.u8()?;
+        let loaded = if reader.version < 4 {
+            None
+        } else {
+            Some(reader.bool()?)
+        };
+        let pattern: [u8; 16] = reader.array()?;
+        // Before version 4 a pattern was always there, silent until one was loaded.
+        let loaded = loaded.unwrap_or(pattern != [0; 16]);
+
+        Ok(Audio {
+            delay,
+            sound,
+            pattern: loaded.then_some(pattern),
+            pitch: reader.u8()?,
+        })
+    }
+
+    /// Count both timers down by one.
     pub fn tick(&mut self) {
         if self.delay > 0 {
             self.delay -= 1
         }
 
         if self.sound > 0 {
-            self.delay -= 1
+            self.sound -= 1
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{MAGIC, VERSION};
+
+    fn reload(audio: &Audio) -> Audio {
+        let mut writer = Writer::new(MAGIC, VERSION);
+        audio.save(&mut writer);
+        let bytes = writer.finish();
+
+        let mut reader = Reader::new(&bytes, MAGIC, VERSION).unwrap();
+        let audio = Audio::load(&mut reader).unwrap();
+        reader.finish().unwrap();
+        audio
+    }
+
+    /// A version 3 state of timers with `pattern`, which was always saved.
+    fn version_3(pattern: [u8; 16]) -> Audio {
+        let mut writer = Writer::new(MAGIC, 3);
+        writer.u8(5);
+        writer.u8(6);
+        writer.bytes(&pattern);
+        writer.u8(70);
+        let bytes = writer.finish();
+
+        let mut reader = Reader::new(&bytes, MAGIC, VERSION).unwrap();
+        let audio = Audio::load(&mut reader).unwrap();
+        reader.finish().unwrap();
+        audio
+    }
+
+    #[test]
+    fn pattern_is_saved_only_once_loaded() {
+        assert_eq!(reload(&Audio::new()).pattern, None);
+
+        let audio = Audio {
+            pattern: Some([0; 16]),
+            ..Audio::new()
+        };
+        assert_eq!(reload(&audio).pattern, Some([0; 16]));
+    }
+
+    #[test]
+    fn version_3_pattern_counts_as_loaded_unless_silent() {
+        let audio = version_3([0; 16]);
+        assert_eq!((audio.delay, audio.sound, audio.pitch), (5, 6, 70));
+        assert_eq!(audio.pattern, None);
+
+        assert_eq!(version_3([0xF0; 16]).pattern, Some([0xF0; 16]));
+    }
+
+    #[test]
+    fn waveform_names() {
+        assert_eq!(Waveform::from_name("Sine"), Some(Waveform::Sine));
+        assert_eq!(Waveform::from_name("noise"), None);
+    }
+}
@@ -0,0 +1,34 @@
+use std::{env, fs, path::PathBuf, process};
+
+use chip8::asm;
+
+const USAGE: &str = "Usage: chip8-asm SOURCE [OUTPUT]
+
+OUTPUT defaults to SOURCE with a .ch8 extension.";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let Some(source_path) = args.first() else {
+        eprintln!("{}", USAGE);
+        process::exit(2)
+    };
+
+    let output_path = match args.get(1) {
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from(source_path).with_extension("ch8"),
+    };
+
+    let image = match asm::assemble_file(source_path) {
+        Ok(image) => image,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    };
+
+    if let Err(err) = fs::write(&output_path, image) {
+        eprintln!("{}: {}", output_path.display(), err);
+        process::exit(1)
+    }
+}
@@ -0,0 +1,38 @@
+use std::{env, path::Path, process};
+
+use chip8::{disasm, Platform, Rom};
+
+const USAGE: &str = "Usage: chip8-disasm ROM [PLATFORM]
+
+PLATFORM is one of chip8, schip or xochip. By default it is guessed from the ROM extension.";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let Some(rom_path) = args.first() else {
+        eprintln!("{}", USAGE);
+        process::exit(2)
+    };
+
+    let platform = match args.get(1) {
+        Some(name) => match Platform::from_name(name) {
+            Some(platform) => platform,
+            None => {
+                eprintln!("Unknown platform {:?}\n\n{}", name, USAGE);
+                process::exit(2)
+            }
+        },
+        None => Path::new(rom_path)
+            .extension()
+            .and_then(|extension| Platform::from_extension(&extension.to_string_lossy()))
+            .unwrap_or_default(),
+    };
+
+    match Rom::open(rom_path) {
+        Ok(rom) => print!("{}", disasm::disassemble(&rom, platform)),
+        Err(err) => {
+            eprintln!("{}: {}", rom_path, err);
+            process::exit(1)
+        }
+    }
+}
@@ -0,0 +1,774 @@
+//! User configuration: defaults for every ROM, and overrides for single ROMs.
+//!
+//! The file is TOML. The `[defaults]` table applies to every ROM, and a `[rom.<sha1>]` table
+//! applies on top of it to the ROM with that SHA-1, in hexadecimal as printed by `chip8 info`,
+//! whatever the file is called. Both take the same settings:
+//!
+//! ```toml
+//! [defaults]
+//! clock = 700                         # instructions per second
+//! palette = ["101010", "33FF66"]      # background, foreground, then the XO-CHIP colours,
+//! # palette = "amber"                 # or a preset
+//! pixels = "grid"                     # square, grid or led
+//! persistence = 4                     # frames pixels fade out over, or "blend" or "off"
+//! screenshots = "~/Pictures/chip8"    # directory screenshots are saved in
+//! tone_freq = 440                     # buzzer frequency in Hz
+//! volume = 0.25                       # buzzer volume from 0 to 1
+//! waveform = "square"                 # square, triangle, sawtooth or sine
+//! rewind_memory = 16                  # MiB kept for rewinding
+//! rewind_interval = 2                 # frames between rewind snapshots
+//!
+//! [rom.0123456789abcdef0123456789abcdef01234567]
+//! name = "Some game"                  # the window title
+//! platform = "schip"
+//! quirks = "schip"                    # a preset, or a table of flags:
+//! # quirks = { preset = "vip", clip = false, display_wait = false }
+//! # quirks = { memory_increment = "by-x" }  # true, false, or I + x as on CHIP-48
+//! keymap = "azerty"                  # a preset, or a table of keys:
+//! # keymap = { preset = "numpad", 5 = ["Up", "W"], 8 = ["Down", "S"] }
+//! ```
+//!
+//! Settings the [ROM database](crate::database) has for the ROM apply between the two tables.
+//! A path starting with `~/` is relative to the home directory.
+//! Keymap entries replace the keyboard keys of one CHIP-8 key, named as SDL names them. A
+//! preset, of quirks or of keys, replaces what was set before it, while single quirks and keys
+//! change only themselves.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{
+    de::{self, value::MapAccessDeserializer, Deserializer, MapAccess, SeqAccess, Visitor},
+    Deserialize,
+};
+
+use crate::{
+    audio::Waveform,
+    error::EmulatorError,
+    keymap::Keymap,
+    palette::{Color, Palette, PixelStyle},
+    platform::Platform,
+    quirks::{MemoryIncrement, Quirks},
+    rom::Rom,
+    video::Persistence,
+};
+
+/// A parsed configuration file.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Settings for every ROM.
+    #[serde(default)]
+    pub defaults: Profile,
+    /// Settings for single ROMs, by lowercase hexadecimal SHA-1.
+    #[serde(default, rename = "rom", deserialize_with = "rom_sections")]
+    pub roms: HashMap<String, Profile>,
+}
+
+impl Config {
+    /// Where the configuration is looked for when none is given: `chip8/config.toml` in the
+    /// user configuration directory.
+    pub fn default_path() -> Option<PathBuf> {
+        let dir = if cfg!(windows) {
+            env::var_os("APPDATA").map(PathBuf::from)
+        } else {
+            env::var_os("XDG_CONFIG_HOME")
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+                .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        };
+
+        dir.map(|dir| dir.join("chip8").join("config.toml"))
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, EmulatorError> {
+        toml::from_str(text)
+            .map_err(|err| EmulatorError::InvalidConfig(err.to_string().trim_end().to_string()))
+    }
+
+    pub fn load_file<P: AsRef<Path>>(path: P) -> Result<Self, EmulatorError> {
+        Config::from_toml(&fs::read_to_string(path).map_err(EmulatorError::ConfigIo)?)
+    }
+
+    /// Load the file at `path`, or an empty configuration if there is no such file.
+    pub fn load_file_or_default<P: AsRef<Path>>(path: P) -> Result<Self, EmulatorError> {
+        match Config::load_file(path) {
+            Err(EmulatorError::ConfigIo(err)) if err.kind() == io::ErrorKind::NotFound => {
+                Ok(Config::default())
+            }
+            config => config,
+        }
+    }
+
+    /// The settings for `rom`: the defaults, then `known`, the settings the ROM is known to
+    /// need such as those from the [ROM database](crate::database), then its own section.
+    pub fn profile(&self, rom: &Rom, known: Option<&Profile>) -> Profile {
+        let mut profile = self.defaults.clone();
+
+        if let Some(known) = known {
+            profile.merge(known);
+        }
+
+        if let Some(overrides) = self.roms.get(&rom.sha1_hex()) {
+            profile.merge(overrides);
+        }
+
+        profile
+    }
+}
+
+/// Settings for running a ROM, each left unset when not given.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Name of the ROM, shown as the window title.
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "platform")]
+    pub platform: Option<Platform>,
+    #[serde(default)]
+    pub quirks: QuirkOverrides,
+    /// Instructions per second.
+    #[serde(default, deserialize_with = "clock")]
+    pub clock: Option<f32>,
+    /// Colours of the pixel values, as in [`Palette`].
+    #[serde(default, deserialize_with = "palette")]
+    pub palette: [Option<Color>; 4],
+    #[serde(default, deserialize_with = "pixels")]
+    pub pixels: Option<PixelStyle>,
+    #[serde(default, deserialize_with = "persistence")]
+    pub persistence: Option<Persistence>,
+    /// Directory screenshots are saved in.
+    #[serde(default, deserialize_with = "path")]
+    pub screenshots: Option<PathBuf>,
+    /// Frequency of the buzzer in Hz.
+    #[serde(default, deserialize_with = "tone_freq")]
+    pub tone_freq: Option<f32>,
+    /// Volume of the buzzer from 0 to 1.
+    #[serde(default, deserialize_with = "volume")]
+    pub volume: Option<f32>,
+    #[serde(default, deserialize_with = "waveform")]
+    pub waveform: Option<Waveform>,
+    /// Memory kept for rewinding, in MiB.
+    #[serde(default, deserialize_with = "positive")]
+    pub rewind_memory: Option<u32>,
+    /// Frames between two rewind snapshots.
+    #[serde(default, deserialize_with = "positive")]
+    pub rewind_interval: Option<u32>,
+    #[serde(default)]
+    pub keymap: KeymapOverrides,
+}
+
+impl Profile {
+    /// Apply the settings `other` sets on top of these.
+    pub fn merge(&mut self, other: &Profile) {
+        if other.name.is_some() {
+            self.name.clone_from(&other.name);
+        }
+
+        self.platform = other.platform.or(self.platform);
+        self.quirks.merge(&other.quirks);
+        self.clock = other.clock.or(self.clock);
+
+        for (color, &other) in self.palette.iter_mut().zip(&other.palette) {
+            *color = other.or(*color);
+        }
+
+        self.pixels = other.pixels.or(self.pixels);
+        self.persistence = other.persistence.or(self.persistence);
+
+        if other.screenshots.is_some() {
+            self.screenshots.clone_from(&other.screenshots);
+        }
+
+        self.tone_freq = other.tone_freq.or(self.tone_freq);
+        self.volume = other.volume.or(self.volume);
+        self.waveform = other.waveform.or(self.waveform);
+        self.rewind_memory = other.rewind_memory.or(self.rewind_memory);
+        self.rewind_interval = other.rewind_interval.or(self.rewind_interval);
+
+        self.keymap.merge(&other.keymap);
+    }
+}
+
+/// A quirk preset and single quirks changed on top of it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QuirkOverrides {
+    pub preset: Option<Quirks>,
+    pub shift: Option<bool>,
+    pub memory_increment: Option<MemoryIncrement>,
+    pub jump: Option<bool>,
+    pub vf_reset: Option<bool>,
+    pub clip: Option<bool>,
+    pub display_wait: Option<bool>,
+}
+
+impl QuirkOverrides {
+    /// Apply the quirks `other` sets on top of these. A preset discards everything before it.
+    pub fn merge(&mut self, other: &QuirkOverrides) {
+        if other.preset.is_some() {
+            *self = *other;
+            return;
+        }
+
+        self.shift = other.shift.or(self.shift);
+        self.memory_increment = other.memory_increment.or(self.memory_increment);
+        self.jump = other.jump.or(self.jump);
+        self.vf_reset = other.vf_reset.or(self.vf_reset);
+        self.clip = other.clip.or(self.clip);
+        self.display_wait = other.display_wait.or(self.display_wait);
+    }
+
+    /// `quirks` with the overrides applied.
+    pub fn apply(&self, quirks: Quirks) -> Quirks {
+        let quirks = self.preset.unwrap_or(quirks);
+
+        Quirks {
+            shift: self.shift.unwrap_or(quirks.shift),
+            memory_increment: self.memory_increment.unwrap_or(quirks.memory_increment),
+            jump: self.jump.unwrap_or(quirks.jump),
+            vf_reset: self.vf_reset.unwrap_or(quirks.vf_reset),
+            clip: self.clip.unwrap_or(quirks.clip),
+            display_wait: self.display_wait.unwrap_or(quirks.display_wait),
+        }
+    }
+}
+
+/// The table form of [`QuirkOverrides`].
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QuirkTable {
+    preset: Option<String>,
+    shift: Option<bool>,
+    #[serde(default, deserialize_with = "memory_increment")]
+    memory_increment: Option<MemoryIncrement>,
+    jump: Option<bool>,
+    vf_reset: Option<bool>,
+    clip: Option<bool>,
+    display_wait: Option<bool>,
+}
+
+fn quirk_preset<E: de::Error>(name: &str) -> Result<Quirks, E> {
+    Quirks::preset(name).ok_or_else(|| {
+        E::custom(one_of(
+            "quirk preset",
+            name,
+            Quirks::PRESETS.iter().map(|(name, _)| *name),
+        ))
+    })
+}
+
+impl<'de> Deserialize<'de> for QuirkOverrides {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct QuirksVisitor;
+
+        impl<'de> Visitor<'de> for QuirksVisitor {
+            type Value = QuirkOverrides;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a quirk preset or a table of quirks")
+            }
+
+            fn visit_str<E: de::Error>(self, name: &str) -> Result<Self::Value, E> {
+                Ok(QuirkOverrides {
+                    preset: Some(quirk_preset(name)?),
+                    ..QuirkOverrides::default()
+                })
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                let table = QuirkTable::deserialize(MapAccessDeserializer::new(map))?;
+
+                Ok(QuirkOverrides {
+                    preset: table.preset.as_deref().map(quirk_preset).transpose()?,
+                    shift: table.shift,
+                    memory_increment: table.memory_increment,
+                    jump: table.jump,
+                    vf_reset: table.vf_reset,
+                    clip: table.clip,
+                    display_wait: table.display_wait,
+                })
+            }
+        }
+
+        deserializer.deserialize_any(QuirksVisitor)
+    }
+}
+
+fn memory_increment<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<MemoryIncrement>, D::Error> {
+    struct MemoryIncrementVisitor;
+
+    impl<'de> Visitor<'de> for MemoryIncrementVisitor {
+        type Value = MemoryIncrement;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "true, false or `by-x`")
+        }
+
+        fn visit_bool<E: de::Error>(self, increment: bool) -> Result<Self::Value, E> {
+            Ok(if increment {
+                MemoryIncrement::PastX
+            } else {
+                MemoryIncrement::Unchanged
+            })
+        }
+
+        fn visit_str<E: de::Error>(self, name: &str) -> Result<Self::Value, E> {
+            if name.eq_ignore_ascii_case("by-x") {
+                Ok(MemoryIncrement::ByX)
+            } else {
+                Err(de::Error::invalid_value(de::Unexpected::Str(name), &self))
+            }
+        }
+    }
+
+    deserializer
+        .deserialize_any(MemoryIncrementVisitor)
+        .map(Some)
+}
+
+/// A keymap preset and CHIP-8 keys bound on top of it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeymapOverrides {
+    pub preset: Option<Keymap>,
+    /// Names of the keyboard keys bound to CHIP-8 keys.
+    pub keys: BTreeMap<usize, Vec<String>>,
+}
+
+impl KeymapOverrides {
+    /// Apply the keys `other` binds on top of these. A preset discards everything before it.
+    pub fn merge(&mut self, other: &KeymapOverrides) {
+        if other.preset.is_some() {
+            self.clone_from(other);
+            return;
+        }
+
+        self.keys.extend(other.keys.clone());
+    }
+
+    /// `keymap` with the overrides applied.
+    pub fn apply(&self, keymap: Keymap) -> Keymap {
+        let mut keymap = self.preset.clone().unwrap_or(keymap);
+
+        for (&key, names) in &self.keys {
+            keymap.bind(key, names.clone());
+        }
+
+        keymap
+    }
+}
+
+fn keymap_preset<E: de::Error>(name: &str) -> Result<Keymap, E> {
+    Keymap::preset(name).ok_or_else(|| {
+        E::custom(one_of(
+            "keymap preset",
+            name,
+            Keymap::PRESETS.iter().map(|(name, _)| *name),
+        ))
+    })
+}
+
+impl<'de> Deserialize<'de> for KeymapOverrides {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct KeymapVisitor;
+
+        impl<'de> Visitor<'de> for KeymapVisitor {
+            type Value = KeymapOverrides;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a keymap preset or a table of keys")
+            }
+
+            fn visit_str<E: de::Error>(self, name: &str) -> Result<Self::Value, E> {
+                Ok(KeymapOverrides {
+                    preset: Some(keymap_preset(name)?),
+                    ..KeymapOverrides::default()
+                })
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut overrides = KeymapOverrides::default();
+
+                while let Some(key) = map.next_key::<String>()? {
+                    if key == "preset" {
+                        overrides.preset = Some(keymap_preset(&map.next_value::<String>()?)?);
+                        continue;
+                    }
+
+                    match usize::from_str_radix(&key, 16) {
+                        Ok(key) if key < 16 => {
+                            let Names(names) = map.next_value()?;
+                            overrides.keys.insert(key, names);
+                        }
+                        _ => {
+                            return Err(de::Error::custom(format!(
+                                "`{}` is not a CHIP-8 key, expected 0 to F or preset",
+                                key
+                            )))
+                        }
+                    }
+                }
+
+                Ok(overrides)
+            }
+        }
+
+        deserializer.deserialize_any(KeymapVisitor)
+    }
+}
+
+/// One string, or a list of them.
+struct Names(Vec<String>);
+
+impl<'de> Deserialize<'de> for Names {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct NamesVisitor;
+
+        impl<'de> Visitor<'de> for NamesVisitor {
+            type Value = Names;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a key name or a list of key names")
+            }
+
+            fn visit_str<E: de::Error>(self, name: &str) -> Result<Self::Value, E> {
+                Ok(Names(vec![name.to_string()]))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut names = Vec::new();
+
+                while let Some(name) = seq.next_element()? {
+                    names.push(name);
+                }
+
+                Ok(Names(names))
+            }
+        }
+
+        deserializer.deserialize_any(NamesVisitor)
+    }
+}
+
+fn one_of<'a, I: IntoIterator<Item = &'a str>>(what: &str, name: &str, names: I) -> String {
+    format!(
+        "unknown {} `{}`, expected one of {}",
+        what,
+        name,
+        names.into_iter().collect::<Vec<_>>().join(", ")
+    )
+}
+
+fn rom_sections<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, Profile>, D::Error> {
+    HashMap::<String, Profile>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(hash, profile)| {
+            if hash.len() == 40 && hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                Ok((hash.to_ascii_lowercase(), profile))
+            } else {
+                Err(de::Error::custom(format!(
+                    "ROM section `{}` is not a SHA-1 in hexadecimal",
+                    hash
+                )))
+            }
+        })
+        .collect()
+}
+
+fn platform<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Platform>, D::Error> {
+    let name = String::deserialize(deserializer)?;
+
+    Platform::from_name(&name).map(Some).ok_or_else(|| {
+        de::Error::custom(one_of(
+            "platform",
+            &name,
+            Platform::NAMES.iter().map(|(name, _)| *name),
+        ))
+    })
+}
+
+fn clock<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f32>, D::Error> {
+    match f64::deserialize(deserializer)? {
+        clock if clock > 0.0 => Ok(Some(clock as f32)),
+        clock => Err(de::Error::custom(format!(
+            "clock must be positive, not {}",
+            clock
+        ))),
+    }
+}
+
+fn path<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<PathBuf>, D::Error> {
+    Ok(Some(expand_home(&String::deserialize(deserializer)?)))
+}
+
+/// `path` with a leading `~` replaced by the home directory, if it is known.
+fn expand_home(path: &str) -> PathBuf {
+    let home = env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" })
+        .filter(|home| !home.is_empty());
+
+    match (path.strip_prefix('~'), home) {
+        (Some(""), Some(home)) => PathBuf::from(home),
+        (Some(rest), Some(home)) if rest.starts_with(['/', '\\']) => {
+            Path::new(&home).join(&rest[1..])
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+fn tone_freq<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f32>, D::Error> {
+    match f64::deserialize(deserializer)? {
+        frequency if frequency > 0.0 => Ok(Some(frequency as f32)),
+        frequency => Err(de::Error::custom(format!(
+            "tone_freq must be positive, not {}",
+            frequency
+        ))),
+    }
+}
+
+fn volume<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f32>, D::Error> {
+    match f64::deserialize(deserializer)? {
+        volume if (0.0..=1.0).contains(&volume) => Ok(Some(volume as f32)),
+        volume => Err(de::Error::custom(format!(
+            "volume must be from 0 to 1, not {}",
+            volume
+        ))),
+    }
+}
+
+fn waveform<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Waveform>, D::Error> {
+    let name = String::deserialize(deserializer)?;
+
+    Waveform::from_name(&name).map(Some).ok_or_else(|| {
+        de::Error::custom(one_of(
+            "waveform",
+            &name,
+            Waveform::NAMES.iter().map(|(name, _)| *name),
+        ))
+    })
+}
+
+fn positive<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    match u32::deserialize(deserializer)? {
+        0 => Err(de::Error::custom("expected a positive whole number, not 0")),
+        value => Ok(Some(value)),
+    }
+}
+
+fn palette<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[Option<Color>; 4], D::Error> {
+    struct PaletteVisitor;
+
+    impl<'de> Visitor<'de> for PaletteVisitor {
+        type Value = [Option<Color>; 4];
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "a palette preset or a list of colours")
+        }
+
+        fn visit_str<E: de::Error>(self, name: &str) -> Result<Self::Value, E> {
+            let palette = Palette::preset(name).ok_or_else(|| {
+                de::Error::custom(one_of(
+                    "palette",
+                    name,
+                    Palette::PRESETS.iter().map(|(name, _)| *name),
+                ))
+            })?;
+
+            Ok(palette.colors.map(Some))
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut palette = [None; 4];
+            let mut len = 0;
+
+            while let Some(hex) = seq.next_element::<String>()? {
+                let color = palette
+                    .get_mut(len)
+                    .ok_or_else(|| de::Error::custom("a palette has at most 4 colours"))?;
+
+                *color = Some(Color::from_hex(&hex).ok_or_else(|| {
+                    de::Error::custom(format!("`{}` is not a colour written as RRGGBB", hex))
+                })?);
+                len += 1;
+            }
+
+            Ok(palette)
+        }
+    }
+
+    deserializer.deserialize_any(PaletteVisitor)
+}
+
+fn pixels<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<PixelStyle>, D::Error> {
+    let name = String::deserialize(deserializer)?;
+
+    PixelStyle::from_name(&name).map(Some).ok_or_else(|| {
+        de::Error::custom(one_of(
+            "pixel style",
+            &name,
+            PixelStyle::NAMES.iter().map(|(name, _)| *name),
+        ))
+    })
+}
+
+fn persistence<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Persistence>, D::Error> {
+    struct PersistenceVisitor;
+
+    impl<'de> Visitor<'de> for PersistenceVisitor {
+        type Value = Persistence;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "a number of frames up to 255, `fade`, `blend` or `off`")
+        }
+
+        fn visit_str<E: de::Error>(self, name: &str) -> Result<Self::Value, E> {
+            Persistence::from_name(name)
+                .ok_or_else(|| de::Error::invalid_value(de::Unexpected::Str(name), &self))
+        }
+
+        fn visit_i64<E: de::Error>(self, frames: i64) -> Result<Self::Value, E> {
+            match u8::try_from(frames) {
+                Ok(0) => Ok(Persistence::Off),
+                Ok(frames) => Ok(Persistence::Fade(frames)),
+                Err(_) => Err(de::Error::invalid_value(
+                    de::Unexpected::Signed(frames),
+                    &self,
+                )),
+            }
+        }
+    }
+
+    deserializer.deserialize_any(PersistenceVisitor).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tone_settings() {
+        let config = Config::from_toml(
+            "[defaults]\ntone_freq = 220\nvolume = 0.5\nwaveform = \"triangle\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(config.defaults.tone_freq, Some(220.0));
+        assert_eq!(config.defaults.volume, Some(0.5));
+        assert_eq!(config.defaults.waveform, Some(Waveform::Triangle));
+
+        assert!(Config::from_toml("[defaults]\nvolume = 2\n").is_err());
+        assert!(Config::from_toml("[defaults]\ntone_freq = 0\n").is_err());
+        assert!(Config::from_toml("[defaults]\nwaveform = \"noise\"\n").is_err());
+    }
+
+    #[test]
+    fn home_directory_is_expanded() {
+        let home = PathBuf::from(env::var_os("HOME").unwrap());
+
+        assert_eq!(expand_home("~/Pictures/chip8"), home.join("Pictures/chip8"));
+        assert_eq!(expand_home("~"), home);
+        assert_eq!(expand_home("~user/shots"), Path::new("~user/shots"));
+        assert_eq!(expand_home("shots/~/x"), Path::new("shots/~/x"));
+
+        let config = Config::from_toml("[defaults]\nscreenshots = \"~/shots\"\n").unwrap();
+        assert_eq!(config.defaults.screenshots, Some(home.join("shots")));
+    }
+
+    #[test]
+    fn memory_increment_quirk() {
+        let quirks = |value: &str| {
+            Config::from_toml(&format!(
+                "[defaults]\nquirks = {{ memory_increment = {} }}",
+                value
+            ))
+            .map(|config| config.defaults.quirks.memory_increment)
+        };
+
+        assert_eq!(quirks("true").unwrap(), Some(MemoryIncrement::PastX));
+        assert_eq!(quirks("false").unwrap(), Some(MemoryIncrement::Unchanged));
+        assert_eq!(quirks("\"by-x\"").unwrap(), Some(MemoryIncrement::ByX));
+        assert!(quirks("\"x\"").is_err());
+    }
+
+    #[test]
+    fn rewind_settings() {
+        let config =
+            Config::from_toml("[defaults]\nrewind_memory = 64\nrewind_interval = 4\n").unwrap();
+
+        assert_eq!(config.defaults.rewind_memory, Some(64));
+        assert_eq!(config.defaults.rewind_interval, Some(4));
+
+        assert!(Config::from_toml("[defaults]\nrewind_memory = 0\n").is_err());
+        assert!(Config::from_toml("[defaults]\nrewind_interval = -1\n").is_err());
+    }
+
+    #[test]
+    fn rom_section_beats_known_settings_beat_defaults() {
+        let rom = Rom::from_bytes(vec![0x12, 0x00]);
+        let config = Config::from_toml(&format!(
+            "[defaults]\nclock = 700\nvolume = 0.5\nquirks = \"vip\"\n\
+             [rom.{}]\nclock = 1500\nquirks = {{ clip = false }}\n",
+            rom.sha1_hex()
+        ))
+        .unwrap();
+        let known = Profile {
+            name: Some("Known".into()),
+            platform: Some(Platform::SuperChip),
+            clock: Some(1000.0),
+            volume: Some(0.75),
+            ..Profile::default()
+        };
+
+        let profile = config.profile(&rom, Some(&known));
+        assert_eq!(profile.name.as_deref(), Some("Known"));
+        assert_eq!(profile.platform, Some(Platform::SuperChip));
+        assert_eq!(profile.clock, Some(1500.0));
+        assert_eq!(profile.volume, Some(0.75));
+        assert_eq!(
+            profile.quirks.apply(Quirks::SUPER_CHIP),
+            Quirks {
+                clip: false,
+                ..Quirks::COSMAC_VIP
+            }
+        );
+
+        let profile = config.profile(&rom, None);
+        assert_eq!(profile.name, None);
+        assert_eq!(profile.clock, Some(1500.0));
+        assert_eq!(profile.volume, Some(0.5));
+
+        let other = Rom::from_bytes(vec![0x12, 0x02]);
+        assert_eq!(config.profile(&other, None), config.defaults);
+    }
+
+    #[test]
+    fn rom_sections_are_keyed_by_sha1() {
+        let rom = Rom::from_bytes(vec![0x12, 0x00]);
+        let config = Config::from_toml(&format!(
+            "[rom.{}]\nname = \"Loop\"\n",
+            rom.sha1_hex().to_ascii_uppercase()
+        ))
+        .unwrap();
+
+        assert!(config.roms.contains_key(&rom.sha1_hex()));
+        assert_eq!(config.profile(&rom, None).name.as_deref(), Some("Loop"));
+
+        let err = Config::from_toml("[rom.pong]\nname = \"Pong\"\n").unwrap_err();
+        assert!(err.to_string().contains("`pong` is not a SHA-1"), "{}", err);
+    }
+
+    #[test]
+    fn unknown_fields_are_refused() {
+        assert!(Config::from_toml("[defaults]\nclock = 700\n").is_ok());
+        assert!(Config::from_toml("[defaults]\nspeed = 700\n").is_err());
+        assert!(Config::from_toml("[settings]\nclock = 700\n").is_err());
+        assert!(Config::from_toml("[defaults]\nquirks = { wrap = true }\n").is_err());
+    }
+}
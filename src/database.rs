@@ -0,0 +1,474 @@
+//! Metadata about known ROMs, in the format of the community
+//! [CHIP-8 database](https://github.com/chip-8/chip-8-database).
+//!
+//! The database is two JSON files: `programs.json`, a list of programs with their title,
+//! authors and the ROM files they were released as, and `sha1-hashes.json`, mapping the SHA-1
+//! of every ROM file to the index of its program. A copy from `data/chip-8-database` is
+//! compiled in, and a directory holding other files in the same format can be loaded and
+//! looked up first.
+//!
+//! An [`Entry`] turns into a [`Profile`] setting the platform, quirks, speed and colours the
+//! ROM needs, so that it runs right without any configuration.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+
+use crate::{
+    config::{Config, Profile},
+    error::EmulatorError,
+    machine::FRAME_RATE,
+    palette::Color,
+    platform::Platform,
+    quirks::{MemoryIncrement, Quirks},
+    rom::Rom,
+};
+
+static EMBEDDED: Lazy<Database> = Lazy::new(|| {
+    Database::from_json(
+        include_str!("../data/chip-8-database/programs.json"),
+        include_str!("../data/chip-8-database/sha1-hashes.json"),
+    )
+    .expect("the embedded ROM database is valid")
+});
+
+/// A program, which may have been released as several ROM files.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Program {
+    pub title: String,
+    pub description: Option<String>,
+    /// Year or date of release.
+    pub release: Option<String>,
+    #[serde(default)]
+    pub authors: Vec<String>,
+    /// The ROM files, by lowercase hexadecimal SHA-1.
+    #[serde(default)]
+    pub roms: HashMap<String, RomEntry>,
+}
+
+/// What is known about one ROM file.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RomEntry {
+    pub file: Option<String>,
+    /// Platforms the ROM runs on, best first, by database id such as `originalChip8`.
+    #[serde(default)]
+    pub platforms: Vec<String>,
+    /// Quirks differing from those of the platform, by platform id.
+    #[serde(default)]
+    pub quirky_platforms: HashMap<String, QuirkFlags>,
+    /// Instructions per frame.
+    pub tickrate: Option<u32>,
+    pub colors: Option<Colors>,
+    /// CHIP-8 keys the game uses, by what they do, such as `up` or `a`.
+    #[serde(default)]
+    pub keys: BTreeMap<String, u8>,
+}
+
+/// Colours the ROM was designed for, as `#RRGGBB`.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Colors {
+    /// Colours of the pixel values, as in [`Palette`](crate::palette::Palette).
+    #[serde(default)]
+    pub pixels: Vec<String>,
+    pub buzzer: Option<String>,
+    pub silence: Option<String>,
+}
+
+/// Quirks as the database names them.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuirkFlags {
+    pub shift: Option<bool>,
+    pub memory_increment_by_x: Option<bool>,
+    pub memory_leave_i_unchanged: Option<bool>,
+    pub wrap: Option<bool>,
+    pub jump: Option<bool>,
+    pub vblank: Option<bool>,
+    pub logic: Option<bool>,
+}
+
+impl QuirkFlags {
+    /// `quirks` with the flags applied.
+    pub fn apply(&self, quirks: Quirks) -> Quirks {
+        Quirks {
+            shift: self.shift.unwrap_or(quirks.shift),
+            memory_increment: match (self.memory_leave_i_unchanged, self.memory_increment_by_x) {
+                (Some(true), _) => MemoryIncrement::Unchanged,
+                (_, Some(true)) => MemoryIncrement::ByX,
+                (None, None) => quirks.memory_increment,
+                _ => MemoryIncrement::PastX,
+            },
+            jump: self.jump.unwrap_or(quirks.jump),
+            vf_reset: self.logic.unwrap_or(quirks.vf_reset),
+            clip: self.wrap.map_or(quirks.clip, |wrap| !wrap),
+            display_wait: self.vblank.unwrap_or(quirks.display_wait),
+        }
+    }
+}
+
+/// The platform and quirks for a database platform id, if it is one that is emulated.
+fn platform(id: &str) -> Option<(Platform, Quirks)> {
+    match id {
+        "originalChip8" | "hybridVIP" => Some((Platform::Chip8, Quirks::COSMAC_VIP)),
+        "modernChip8" => Some((
+            Platform::Chip8,
+            Quirks {
+                vf_reset: false,
+                display_wait: false,
+                ..Quirks::COSMAC_VIP
+            },
+        )),
+        "chip48" => Some((Platform::Chip8, Quirks::CHIP_48)),
+        "superchip1" | "superchip" => Some((Platform::SuperChip, Quirks::SUPER_CHIP)),
+        "xochip" => Some((Platform::XoChip, Quirks::XO_CHIP)),
+        _ => None,
+    }
+}
+
+/// A program and one of its ROM files.
+#[derive(Clone, Copy, Debug)]
+pub struct Entry<'a> {
+    pub program: &'a Program,
+    pub rom: &'a RomEntry,
+}
+
+impl Entry<'_> {
+    /// Settings to run the ROM with, named after the program.
+    pub fn profile(&self) -> Profile {
+        let mut profile = Profile {
+            name: Some(self.program.title.clone()),
+            ..Profile::default()
+        };
+
+        let supported = self
+            .rom
+            .platforms
+            .iter()
+            .find_map(|id| platform(id).map(|(platform, quirks)| (id, platform, quirks)));
+
+        if let Some((id, platform, quirks)) = supported {
+            let flags = self
+                .rom
+                .quirky_platforms
+                .get(id)
+                .copied()
+                .unwrap_or_default();
+            profile.platform = Some(platform);
+            profile.quirks.preset = Some(flags.apply(quirks));
+        }
+
+        if let Some(tickrate) = self.rom.tickrate.filter(|&tickrate| tickrate > 0) {
+            profile.clock = Some(tickrate as f32 * FRAME_RATE);
+        }
+
+        if let Some(colors) = &self.rom.colors {
+            for (color, hex) in profile.palette.iter_mut().zip(&colors.pixels) {
+                *color = Color::from_hex(hex);
+            }
+        }
+
+        profile
+    }
+}
+
+/// A set of known programs.
+#[derive(Clone, Debug, Default)]
+pub struct Database {
+    programs: Vec<Program>,
+    /// Index in `programs` of every ROM, by lowercase hexadecimal SHA-1.
+    hashes: HashMap<String, usize>,
+}
+
+impl Database {
+    /// The database compiled into this build.
+    pub fn embedded() -> &'static Database {
+        &EMBEDDED
+    }
+
+    /// Where a local database is looked for when none is given: `chip8/database` in the user
+    /// configuration directory, next to the configuration file.
+    pub fn default_dir() -> Option<PathBuf> {
+        Config::default_path().map(|path| path.with_file_name("database"))
+    }
+
+    /// Parse the contents of `programs.json` and `sha1-hashes.json`.
+    pub fn from_json(programs: &str, hashes: &str) -> Result<Self, EmulatorError> {
+        let invalid = |err: serde_json::Error| EmulatorError::InvalidDatabase(err.to_string());
+
+        let programs: Vec<Program> = serde_json::from_str(programs).map_err(invalid)?;
+        let hashes: HashMap<String, usize> = serde_json::from_str(hashes).map_err(invalid)?;
+
+        if let Some((hash, &index)) = hashes.iter().find(|(_, &index)| index >= programs.len()) {
+            return Err(EmulatorError::InvalidDatabase(format!(
+                "ROM {} belongs to program {}, but there are only {}",
+                hash,
+                index,
+                programs.len()
+            )));
+        }
+
+        let hashes = hashes
+            .into_iter()
+            .map(|(hash, index)| (hash.to_ascii_lowercase(), index))
+            .collect();
+
+        Ok(Database { programs, hashes })
+    }
+
+    /// Load `programs.json` and `sha1-hashes.json` from `dir`.
+    pub fn load_dir<P: AsRef<Path>>(dir: P) -> Result<Self, EmulatorError> {
+        let read = |name: &str| {
+            fs::read_to_string(dir.as_ref().join(name)).map_err(EmulatorError::DatabaseIo)
+        };
+
+        Database::from_json(&read("programs.json")?, &read("sha1-hashes.json")?)
+    }
+
+    /// Load the database in `dir`, or `None` if there is no such directory.
+    pub fn load_dir_if_exists<P: AsRef<Path>>(dir: P) -> Result<Option<Self>, EmulatorError> {
+        match Database::load_dir(dir) {
+            Ok(database) => Ok(Some(database)),
+            Err(EmulatorError::DatabaseIo(err)) if err.kind() == io::ErrorKind::NotFound => {
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Number of programs.
+    pub fn len(&self) -> usize {
+        self.programs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.programs.is_empty()
+    }
+
+    /// What the first of `databases` that knows `rom` has about it, such as a local database
+    /// and then the [embedded](Database::embedded) one.
+    pub fn lookup_first<'a, I>(databases: I, rom: &Rom) -> Option<Entry<'a>>
+    where
+        I: IntoIterator<Item = &'a Database>,
+    {
+        databases
+            .into_iter()
+            .find_map(|database| database.lookup(rom))
+    }
+
+    /// What is known about `rom`, if anything.
+    pub fn lookup(&self, rom: &Rom) -> Option<Entry<'_>> {
+        let hash = rom.sha1_hex();
+        let program = &self.programs[*self.hashes.get(&hash)?];
+        let rom = program
+            .roms
+            .iter()
+            .find(|(rom, _)| rom.eq_ignore_ascii_case(&hash))?
+            .1;
+
+        Some(Entry { program, rom })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    fn rom() -> Rom {
+        Rom::from_bytes(vec![0x12, 0x00])
+    }
+
+    /// A database knowing [`rom`] as a program called `title`, with the hash in upper case in
+    /// `sha1-hashes.json` and in mixed case in `programs.json`.
+    fn database(title: &str) -> Database {
+        let hash = rom().sha1_hex();
+        let mixed: String = hash
+            .chars()
+            .enumerate()
+            .map(|(index, c)| {
+                if index % 2 == 0 {
+                    c.to_ascii_uppercase()
+                } else {
+                    c
+                }
+            })
+            .collect();
+
+        let programs = format!(
+            r##"[
+                {{ "title": "Other", "roms": {{}} }},
+                {{
+                    "title": "{}",
+                    "authors": ["Someone"],
+                    "release": "1978",
+                    "roms": {{
+                        "{}": {{
+                            "file": "loop.ch8",
+                            "platforms": ["megachip8", "superchip", "originalChip8"],
+                            "quirkyPlatforms": {{ "superchip": {{ "shift": false }} }},
+                            "tickrate": 20,
+                            "colors": {{ "pixels": ["#000000", "#FF8800"] }},
+                            "keys": {{ "up": 5, "down": 8 }}
+                        }}
+                    }}
+                }}
+            ]"##,
+            title, mixed
+        );
+        let hashes = format!(r#"{{ "{}": 1 }}"#, hash.to_ascii_uppercase());
+
+        Database::from_json(&programs, &hashes).unwrap()
+    }
+
+    #[test]
+    fn parse_and_look_up() {
+        let database = database("Loop");
+        assert_eq!(database.len(), 2);
+
+        let entry = database.lookup(&rom()).unwrap();
+        assert_eq!(entry.program.title, "Loop");
+        assert_eq!(entry.program.authors, ["Someone"]);
+        assert_eq!(entry.rom.file.as_deref(), Some("loop.ch8"));
+        assert_eq!(entry.rom.keys["up"], 5);
+
+        assert!(database
+            .lookup(&Rom::from_bytes(vec![0x12, 0x02]))
+            .is_none());
+    }
+
+    #[test]
+    fn invalid_database_is_refused() {
+        assert!(matches!(
+            Database::from_json("[]", r#"{ "00": 0 }"#),
+            Err(EmulatorError::InvalidDatabase(_))
+        ));
+        assert!(matches!(
+            Database::from_json("{}", "{}"),
+            Err(EmulatorError::InvalidDatabase(_))
+        ));
+    }
+
+    #[test]
+    fn entry_profile() {
+        let database = database("Loop");
+        let profile = database.lookup(&rom()).unwrap().profile();
+
+        // megachip8 isn't emulated, so the next platform is picked, with its quirks.
+        assert_eq!(profile.name.as_deref(), Some("Loop"));
+        assert_eq!(profile.platform, Some(Platform::SuperChip));
+        assert_eq!(
+            profile.quirks.preset,
+            Some(Quirks {
+                shift: false,
+                ..Quirks::SUPER_CHIP
+            })
+        );
+        assert_eq!(profile.clock, Some(20.0 * FRAME_RATE));
+        assert_eq!(profile.palette[1], Color::from_hex("FF8800"));
+        assert_eq!(profile.palette[2], None);
+    }
+
+    #[test]
+    fn platform_ids() {
+        assert_eq!(
+            platform("originalChip8"),
+            Some((Platform::Chip8, Quirks::COSMAC_VIP))
+        );
+        assert_eq!(platform("chip48"), Some((Platform::Chip8, Quirks::CHIP_48)));
+        assert_eq!(
+            platform("superchip1"),
+            Some((Platform::SuperChip, Quirks::SUPER_CHIP))
+        );
+        assert_eq!(
+            platform("xochip"),
+            Some((Platform::XoChip, Quirks::XO_CHIP))
+        );
+        assert_eq!(platform("megachip8"), None);
+
+        let (_, modern) = platform("modernChip8").unwrap();
+        assert!(!modern.vf_reset && !modern.display_wait);
+    }
+
+    #[test]
+    fn memory_quirk_flags() {
+        let flags = |leave_i_unchanged, increment_by_x| QuirkFlags {
+            memory_leave_i_unchanged: leave_i_unchanged,
+            memory_increment_by_x: increment_by_x,
+            ..QuirkFlags::default()
+        };
+        let increment = |flags: QuirkFlags| flags.apply(Quirks::CHIP_48).memory_increment;
+
+        assert_eq!(increment(flags(None, None)), MemoryIncrement::ByX);
+        assert_eq!(
+            increment(flags(Some(true), None)),
+            MemoryIncrement::Unchanged
+        );
+        assert_eq!(
+            increment(flags(Some(true), Some(true))),
+            MemoryIncrement::Unchanged
+        );
+        assert_eq!(increment(flags(None, Some(true))), MemoryIncrement::ByX);
+        assert_eq!(increment(flags(Some(false), None)), MemoryIncrement::PastX);
+        assert_eq!(increment(flags(None, Some(false))), MemoryIncrement::PastX);
+        assert_eq!(
+            increment(flags(Some(false), Some(false))),
+            MemoryIncrement::PastX
+        );
+    }
+
+    #[test]
+    fn other_quirk_flags() {
+        let flags = QuirkFlags {
+            shift: Some(true),
+            wrap: Some(true),
+            jump: Some(true),
+            vblank: Some(false),
+            logic: Some(false),
+            ..QuirkFlags::default()
+        };
+
+        assert_eq!(
+            flags.apply(Quirks::COSMAC_VIP),
+            Quirks {
+                shift: true,
+                clip: false,
+                jump: true,
+                display_wait: false,
+                vf_reset: false,
+                ..Quirks::COSMAC_VIP
+            }
+        );
+    }
+
+    #[test]
+    fn missing_directory_is_no_database() {
+        let missing = env::temp_dir().join(format!("chip8-no-database-{}", process::id()));
+        assert!(matches!(Database::load_dir_if_exists(&missing), Ok(None)));
+        assert!(matches!(
+            Database::load_dir(&missing),
+            Err(EmulatorError::DatabaseIo(_))
+        ));
+    }
+
+    #[test]
+    fn first_database_knowing_the_rom_wins() {
+        let local = database("Local");
+        let embedded = database("Embedded");
+        let empty = Database::default();
+
+        let entry = Database::lookup_first([&local, &embedded], &rom()).unwrap();
+        assert_eq!(entry.program.title, "Local");
+
+        let entry = Database::lookup_first([&empty, &embedded], &rom()).unwrap();
+        assert_eq!(entry.program.title, "Embedded");
+
+        assert!(Database::lookup_first([&empty], &rom()).is_none());
+    }
+}
@@ -0,0 +1,437 @@
+use std::collections::BTreeSet;
+
+use crate::{error::EmulatorError, instruction::Instruction, machine::Machine};
+
+const HELP: &str = "\
+Commands:
+  b, break ADDR        set a breakpoint
+  d, delete ADDR       remove a breakpoint
+  l, list              list breakpoints
+  s, step [N]          execute N instructions (default 1)
+  n, next              step over a CALL
+  o, out               run until the current subroutine returns
+  c, continue          resume execution
+  r, regs              show registers, timers and the stack
+  x, mem ADDR [LEN]    dump LEN bytes of memory from ADDR (default 64)
+  q, quit              close the emulator
+  h, help              show this help
+Addresses and lengths are hexadecimal.";
+
+/// Condition ending a run started by `next`, `out` or `continue`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Target {
+    /// Run until a breakpoint.
+    Breakpoint,
+    /// Run until the call at the current depth returns to `pc`.
+    Return { pc: usize, depth: usize },
+    /// Run until the stack is shallower than `depth`.
+    Out { depth: usize },
+}
+
+/// An interactive debugger controlling a [`Machine`].
+///
+/// The debugger doesn't read input itself; the frontend passes it command lines with
+/// [`Debugger::command`], and runs the machine through [`Debugger::run_frame`] so that
+/// breakpoints are honoured.
+#[derive(Default)]
+pub struct Debugger {
+    breakpoints: BTreeSet<usize>,
+    paused: bool,
+    target: Option<Target>,
+    /// Whether execution just resumed, so that the breakpoint it resumes from, already hit,
+    /// doesn't stop it again.
+    resumed: bool,
+    /// Whether `step` and `next` are refused.
+    stepping_refused: bool,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Debugger::default()
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Stop execution and show where it stopped.
+    pub fn pause(&mut self, machine: &Machine) {
+        self.paused = true;
+        self.target = None;
+        println!("Paused at {}", Self::location(machine));
+    }
+
+    /// Refuse `step` and `next`, or accept them again. They run instructions outside of
+    /// [`Debugger::run_frame`], and so outside of the frames a movie records.
+    pub fn refuse_stepping(&mut self, refused: bool) {
+        self.stepping_refused = refused
+    }
+
+    /// Run the rest of the current frame, unless paused, stopping early at a breakpoint or
+    /// when the target of `next` or `out` is reached. Returns whether the frame was completed.
+    ///
+    /// Errors are returned while the debugger isn't in use. Once it is, they are reported and
+    /// the machine pauses on the faulting instruction, so that it can be looked into.
+    pub fn run_frame(&mut self, machine: &mut Machine) -> Result<bool, EmulatorError> {
+        while !self.paused {
+            if !self.resumed && self.breakpoints.contains(&machine.pc()) {
+                println!("Breakpoint at {:#05X}", machine.pc());
+                self.pause(machine);
+                break;
+            }
+
+            self.resumed = false;
+
+            let frame_ended = match machine.step() {
+                Ok(frame_ended) => frame_ended,
+                Err(err) if self.target.is_some() => {
+                    println!("Error: {}", err);
+                    self.pause(machine);
+                    break;
+                }
+                Err(err) => return Err(err),
+            };
+
+            if self.target_reached(machine) {
+                self.pause(machine);
+            }
+
+            if frame_ended {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Execute one command line. Returns `false` if the user asked to quit.
+    pub fn command(&mut self, machine: &mut Machine, line: &str) -> Result<bool, EmulatorError> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("");
+        let args: Vec<&str> = words.collect();
+
+        match command {
+            "" => {}
+            "b" | "break" => match Self::address(&args, 0) {
+                Some(address) => {
+                    self.breakpoints.insert(address);
+                    println!("Breakpoint set at {:#05X}", address)
+                }
+                None => println!("Usage: break ADDR"),
+            },
+            "d" | "delete" => match Self::address(&args, 0) {
+                Some(address) if self.breakpoints.remove(&address) => {
+                    println!("Breakpoint removed at {:#05X}", address)
+                }
+                Some(address) => println!("No breakpoint at {:#05X}", address),
+                None => println!("Usage: delete ADDR"),
+            },
+            "l" | "list" => {
+                for address in &self.breakpoints {
+                    println!("{:#05X}", address)
+                }
+            }
+            "s" | "step" | "n" | "next" if self.stepping_refused => {
+                println!("Cannot step while a movie is recording or playing, use continue")
+            }
+            "s" | "step" => {
+                let count = args.first().and_then(|n| n.parse().ok()).unwrap_or(1);
+
+                for _ in 0..count {
+                    if !Self::step(machine) {
+                        break;
+                    }
+                }
+
+                self.pause(machine)
+            }
+            "n" | "next" => {
+                if let Ok(Instruction::Call(_)) = machine.instruction_at(machine.pc()) {
+                    self.resume(Target::Return {
+                        pc: machine.pc() + 2,
+                        depth: machine.stack().len(),
+                    })
+                } else {
+                    Self::step(machine);
+                    self.pause(machine)
+                }
+            }
+            "o" | "out" => {
+                if machine.stack().is_empty() {
+                    println!("Not in a subroutine")
+                } else {
+                    self.resume(Target::Out {
+                        depth: machine.stack().len(),
+                    })
+                }
+            }
+            "c" | "continue" => self.resume(Target::Breakpoint),
+            "r" | "regs" => Self::print_registers(machine),
+            "x" | "mem" => match Self::address(&args, 0) {
+                Some(address) => {
+                    let len = Self::address(&args, 1).unwrap_or(64);
+                    Self::print_memory(machine, address, len)
+                }
+                None => println!("Usage: mem ADDR [LEN]"),
+            },
+            "q" | "quit" => return Ok(false),
+            "h" | "help" => println!("{}", HELP),
+            _ => println!("Unknown command {:?}, try help", command),
+        }
+
+        Ok(true)
+    }
+
+    fn resume(&mut self, target: Target) {
+        self.paused = false;
+        self.target = Some(target);
+        self.resumed = true;
+    }
+
+    /// Execute one instruction, reporting an error instead. Returns whether it ran.
+    fn step(machine: &mut Machine) -> bool {
+        match machine.step() {
+            Ok(_) => true,
+            Err(err) => {
+                println!("Error: {}", err);
+                false
+            }
+        }
+    }
+
+    fn target_reached(&self, machine: &Machine) -> bool {
+        let depth = machine.stack().len();
+
+        match self.target {
+            Some(Target::Return { pc, depth: call }) => machine.pc() == pc && depth == call,
+            Some(Target::Out { depth: call }) => depth < call,
+            Some(Target::Breakpoint) | None => false,
+        }
+    }
+
+    fn location(machine: &Machine) -> String {
+        match machine.instruction_at(machine.pc()) {
+            Ok(instruction) => format!("{:#05X}: {}", machine.pc(), instruction),
+            Err(err) => format!("{:#05X}: {}", machine.pc(), err),
+        }
+    }
+
+    fn address(args: &[&str], index: usize) -> Option<usize> {
+        let arg = args.get(index)?;
+        let digits = arg.trim_start_matches("0x").trim_start_matches("0X");
+        usize::from_str_radix(digits, 16).ok()
+    }
+
+    fn print_registers(machine: &Machine) {
+        for (row, registers) in machine.v().chunks(8).enumerate() {
+            let registers: Vec<String> = registers
+                .iter()
+                .enumerate()
+                .map(|(index, value)| format!("V{:X}={:02X}", row * 8 + index, value))
+                .collect();
+
+            println!("{}", registers.join(" "))
+        }
+
+        println!(
+            "PC={:#05X} I={:#05X} SP={:X} DT={:02X} ST={:02X}",
+            machine.pc(),
+            machine.i(),
+            machine.stack().len(),
+            machine.audio.delay,
+            machine.audio.sound
+        );
+
+        let stack: Vec<String> = machine
+            .stack()
+            .iter()
+            .map(|address| format!("{:#05X}", address))
+            .collect();
+
+        println!("Stack: [{}]", stack.join(", "))
+    }
+
+    fn print_memory(machine: &Machine, address: usize, len: usize) {
+        for line in Self::memory_lines(machine.ram(), address, len) {
+            println!("{}", line)
+        }
+    }
+
+    /// Lines of a dump of `len` bytes of `ram` from `address`, 16 to a line, cut at the end of
+    /// memory.
+    fn memory_lines(ram: &[u8], address: usize, len: usize) -> Vec<String> {
+        if address >= ram.len() {
+            return vec![format!("Address {:#05X} is out of range", address)];
+        }
+
+        if len == 0 {
+            return vec![format!("Empty range at {:#05X}", address)];
+        }
+
+        let end = address.saturating_add(len).min(ram.len());
+
+        (address..end)
+            .step_by(16)
+            .map(|line| {
+                let bytes = &ram[line..(line + 16).min(end)];
+                let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+                format!("{:#06X}: {}", line, hex.join(" "))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rom::Rom;
+
+    fn machine(program: &[u8]) -> Machine {
+        let mut machine = Machine::new();
+        machine.instructions_per_frame = 1;
+        machine.load(&Rom::from_bytes(program.to_vec())).unwrap();
+        machine
+    }
+
+    #[test]
+    fn breakpoint_at_start_of_frame() {
+        // LD V0, 1; LD V1, 2; JP 0x204
+        let mut machine = machine(&[0x60, 0x01, 0x61, 0x02, 0x12, 0x04]);
+        let mut debugger = Debugger::new();
+
+        debugger.command(&mut machine, "break 202").unwrap();
+        debugger.command(&mut machine, "continue").unwrap();
+        debugger.run_frame(&mut machine).unwrap();
+        assert!(!debugger.paused());
+
+        debugger.run_frame(&mut machine).unwrap();
+        assert!(debugger.paused());
+        assert_eq!(machine.pc(), 0x202);
+        assert_eq!(machine.v()[1], 0);
+
+        // Continuing doesn't stop at the breakpoint just hit.
+        debugger.command(&mut machine, "continue").unwrap();
+        debugger.run_frame(&mut machine).unwrap();
+        assert!(!debugger.paused());
+        assert_eq!(machine.v()[1], 2);
+    }
+
+    #[test]
+    fn error_pauses_when_debugging() {
+        // LD V0, 1; an invalid opcode
+        let mut machine = machine(&[0x60, 0x01, 0x50, 0x01]);
+        let mut debugger = Debugger::new();
+
+        debugger.command(&mut machine, "step 5").unwrap();
+        assert!(debugger.paused());
+        assert_eq!(machine.pc(), 0x202);
+
+        debugger.command(&mut machine, "continue").unwrap();
+        debugger.run_frame(&mut machine).unwrap();
+        assert!(debugger.paused());
+        assert_eq!(machine.pc(), 0x202);
+    }
+
+    #[test]
+    fn error_is_returned_when_not_debugging() {
+        let mut machine = machine(&[0x50, 0x01]);
+        let mut debugger = Debugger::new();
+
+        assert!(matches!(
+            debugger.run_frame(&mut machine),
+            Err(EmulatorError::InvalidOpcode { address: 0x200, .. })
+        ));
+    }
+
+    /// Run frames until the debugger pauses.
+    fn run_until_paused(debugger: &mut Debugger, machine: &mut Machine) {
+        for _ in 0..100 {
+            if debugger.paused() {
+                return;
+            }
+
+            debugger.run_frame(machine).unwrap();
+        }
+
+        panic!("the debugger didn't pause");
+    }
+
+    // CALL 0x206; LD V1, 1; JP 0x204; LD V0, 5; RET
+    const CALL: [u8; 10] = [0x22, 0x06, 0x61, 0x01, 0x12, 0x04, 0x60, 0x05, 0x00, 0xEE];
+
+    #[test]
+    fn next_steps_over_a_call() {
+        let mut machine = machine(&CALL);
+        let mut debugger = Debugger::new();
+
+        debugger.command(&mut machine, "next").unwrap();
+        run_until_paused(&mut debugger, &mut machine);
+        assert_eq!(machine.pc(), 0x202);
+        assert_eq!(machine.v()[0], 5);
+        assert!(machine.stack().is_empty());
+
+        debugger.command(&mut machine, "next").unwrap();
+        assert!(debugger.paused());
+        assert_eq!(machine.pc(), 0x204);
+        assert_eq!(machine.v()[1], 1);
+    }
+
+    #[test]
+    fn out_runs_until_the_subroutine_returns() {
+        let mut machine = machine(&CALL);
+        let mut debugger = Debugger::new();
+
+        // Not in a subroutine yet.
+        debugger.command(&mut machine, "out").unwrap();
+        assert_eq!(machine.pc(), 0x200);
+
+        debugger.command(&mut machine, "step").unwrap();
+        assert_eq!(machine.pc(), 0x206);
+
+        debugger.command(&mut machine, "out").unwrap();
+        run_until_paused(&mut debugger, &mut machine);
+        assert_eq!(machine.pc(), 0x202);
+        assert_eq!(machine.v()[0], 5);
+        assert_eq!(machine.v()[1], 0);
+    }
+
+    #[test]
+    fn memory_dump_bounds() {
+        let ram: Vec<u8> = (0..0x1000).map(|address| address as u8).collect();
+
+        assert_eq!(
+            Debugger::memory_lines(&ram, 0x200, 0x14),
+            [
+                "0x0200: 00 01 02 03 04 05 06 07 08 09 0A 0B 0C 0D 0E 0F",
+                "0x0210: 10 11 12 13",
+            ]
+        );
+        assert_eq!(
+            Debugger::memory_lines(&ram, 0xFFE, usize::MAX),
+            ["0x0FFE: FE FF"]
+        );
+        assert_eq!(
+            Debugger::memory_lines(&ram, 0x200, 0),
+            ["Empty range at 0x200"]
+        );
+        assert_eq!(
+            Debugger::memory_lines(&ram, 0x1000, 4),
+            ["Address 0x1000 is out of range"]
+        );
+    }
+
+    #[test]
+    fn stepping_can_be_refused() {
+        let mut machine = machine(&CALL);
+        let mut debugger = Debugger::new();
+
+        debugger.refuse_stepping(true);
+        debugger.command(&mut machine, "step").unwrap();
+        debugger.command(&mut machine, "next").unwrap();
+        assert_eq!(machine.pc(), 0x200);
+
+        debugger.refuse_stepping(false);
+        debugger.command(&mut machine, "step").unwrap();
+        assert_eq!(machine.pc(), 0x206);
+    }
+}
@@ -0,0 +1,304 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use crate::{instruction::Instruction, machine::PROGRAM_START, platform::Platform, rom::Rom};
+
+/// Most bytes shown on one listing line.
+const BYTES_PER_LINE: usize = 4;
+
+/// A ROM split into code and data.
+///
+/// Code is found by following every path the program can take from [`PROGRAM_START`]:
+/// through jumps and calls, both outcomes of skips, and on until a return, an exit, or a
+/// computed `JP V0` whose target can't be known. Whatever is never reached is listed as data.
+///
+/// Formatting it gives the annotated listing: one line per instruction or run of data, with
+/// its address, raw bytes and mnemonic, and labels on every jump and call target. A target in
+/// the middle of another instruction gets its label as a constant instead, and its instruction
+/// as a comment.
+pub struct Disassembly<'a> {
+    rom: &'a [u8],
+    platform: Platform,
+    code: BTreeMap<usize, Instruction>,
+    labels: BTreeMap<usize, String>,
+}
+
+/// Disassemble `rom` as a program for `platform`.
+pub fn disassemble(rom: &Rom, platform: Platform) -> Disassembly<'_> {
+    let mut disassembly = Disassembly {
+        rom: rom.bytes(),
+        platform,
+        code: BTreeMap::new(),
+        labels: BTreeMap::new(),
+    };
+
+    disassembly.trace();
+    disassembly
+}
+
+impl<'a> Disassembly<'a> {
+    /// The instructions found, by address.
+    pub fn code(&self) -> &BTreeMap<usize, Instruction> {
+        &self.code
+    }
+
+    /// Label names, by address.
+    pub fn labels(&self) -> &BTreeMap<usize, String> {
+        &self.labels
+    }
+
+    fn trace(&mut self) {
+        let mut pending = vec![PROGRAM_START];
+        let mut calls = BTreeSet::new();
+        let mut jumps = BTreeSet::new();
+
+        while let Some(address) = pending.pop() {
+            if self.code.contains_key(&address) {
+                continue;
+            }
+
+            let Some(instruction) = self.decode(address) else {
+                continue;
+            };
+
+            self.code.insert(address, instruction);
+            let next = address + instruction.length();
+
+            match instruction {
+                Instruction::Jump(target) => {
+                    jumps.insert(target as usize);
+                    pending.push(target as usize)
+                }
+                Instruction::Call(target) => {
+                    calls.insert(target as usize);
+                    pending.push(target as usize);
+                    pending.push(next)
+                }
+                Instruction::Return | Instruction::Exit | Instruction::JumpOffset(_) => {}
+                _ if instruction.is_skip() => {
+                    let skipped = self.decode(next).map_or(2, |skipped| skipped.length());
+                    pending.push(next + skipped);
+                    pending.push(next)
+                }
+                _ => pending.push(next),
+            }
+        }
+
+        calls.retain(|&address| self.contains(address));
+        jumps.retain(|&address| self.contains(address));
+
+        for address in calls {
+            self.labels.insert(address, format!("sub_{:03X}", address));
+        }
+
+        for address in jumps {
+            self.labels
+                .entry(address)
+                .or_insert_with(|| format!("label_{:03X}", address));
+        }
+    }
+
+    fn contains(&self, address: usize) -> bool {
+        (PROGRAM_START..PROGRAM_START + self.rom.len()).contains(&address)
+    }
+
+    fn word(&self, address: usize) -> Option<u16> {
+        let offset = address.checked_sub(PROGRAM_START)?;
+        let bytes = self.rom.get(offset..offset + 2)?;
+        Some((bytes[0] as u16) << 8 | bytes[1] as u16)
+    }
+
+    /// Decode the instruction at `address`, if it is a valid one lying entirely in the ROM.
+    fn decode(&self, address: usize) -> Option<Instruction> {
+        let opcode = self.word(address)?;
+        let next = self.word(address + 2).unwrap_or(0);
+        let instruction = Instruction::decode(opcode, next, self.platform)?;
+
+        if self.contains(address + instruction.length() - 1) {
+            Some(instruction)
+        } else {
+            None
+        }
+    }
+
+    fn write_line(&self, f: &mut fmt::Formatter<'_>, address: usize, bytes: &[u8]) -> fmt::Result {
+        if let Some(label) = self.labels.get(&address) {
+            writeln!(f, "{}:", label)?;
+        }
+
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        write!(
+            f,
+            "{:#05X}  {:<width$}  ",
+            address,
+            hex.join(" "),
+            width = BYTES_PER_LINE * 3 - 1
+        )
+    }
+}
+
+impl fmt::Display for Disassembly<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "; platform: {}", self.platform.name())?;
+
+        let label = |address: u16| {
+            self.labels
+                .get(&(address as usize))
+                .cloned()
+                .unwrap_or_else(|| format!("{:#05X}", address))
+        };
+
+        let end = PROGRAM_START + self.rom.len();
+        let mut address = PROGRAM_START;
+
+        while address < end {
+            let offset = address - PROGRAM_START;
+
+            if let Some(instruction) = self.code.get(&address) {
+                let length = instruction.length();
+                self.write_line(f, address, &self.rom[offset..offset + length])?;
+                instruction.fmt_with(f, &label)?;
+                writeln!(f)?;
+
+                // Instructions traced in the middle of this one can't have a line of their
+                // own: their labels become constants and they are shown as comments.
+                for inner in address + 1..address + length {
+                    if let Some(name) = self.labels.get(&inner) {
+                        writeln!(f, "{} = {:#05X}", name, inner)?;
+                    }
+
+                    if let Some(overlapped) = self.code.get(&inner) {
+                        write!(f, "; {:#05X}  ", inner)?;
+                        overlapped.fmt_with(f, &label)?;
+                        writeln!(f)?;
+                    }
+                }
+
+                address += length;
+                continue;
+            }
+
+            // Data runs until the next instruction or label, whichever is closer.
+            let mut length = 1;
+
+            while length < BYTES_PER_LINE
+                && address + length < end
+                && !self.code.contains_key(&(address + length))
+                && !self.labels.contains_key(&(address + length))
+            {
+                length += 1
+            }
+
+            let bytes = &self.rom[offset..offset + length];
+            let data: Vec<String> = bytes.iter().map(|byte| format!("{:#04X}", byte)).collect();
+            self.write_line(f, address, bytes)?;
+            writeln!(f, "db {}", data.join(", "))?;
+
+            address += length;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm;
+
+    fn assemble(source: &str) -> Rom {
+        Rom::from_bytes(asm::assemble(source).unwrap())
+    }
+
+    fn code_addresses(disassembly: &Disassembly) -> Vec<usize> {
+        disassembly.code().keys().copied().collect()
+    }
+
+    #[test]
+    fn code_and_data() {
+        let rom =
+            assemble("LD I, sprite\nDRW V0, V0, 2\nJP $\nsprite: db 0xF0, 0x90, 0x90, 0xF0, 0x42");
+        let disassembly = disassemble(&rom, Platform::Chip8);
+
+        assert_eq!(
+            disassembly.to_string(),
+            "\
+; platform: chip8
+0x200  A2 06        LD I, 0x206
+0x202  D0 02        DRW V0, V0, 2
+label_204:
+0x204  12 04        JP label_204
+0x206  F0 90 90 F0  db 0xF0, 0x90, 0x90, 0xF0
+0x20A  42           db 0x42
+"
+        );
+    }
+
+    #[test]
+    fn both_arms_of_a_skip() {
+        let rom = assemble("SE V0, 1\nJP end\nCLS\nend: EXIT\ndb 0xFF");
+        let disassembly = disassemble(&rom, Platform::SuperChip);
+        assert_eq!(code_addresses(&disassembly), [0x200, 0x202, 0x204, 0x206]);
+
+        // The skip steps over the whole 4-byte F000 nnnn.
+        let rom = assemble("SNE V0, 1\nLD I, long 0x1234\nEXIT");
+        let disassembly = disassemble(&rom, Platform::XoChip);
+        assert_eq!(code_addresses(&disassembly), [0x200, 0x202, 0x206]);
+    }
+
+    #[test]
+    fn jump_and_call_targets_are_labelled() {
+        let rom = assemble("CALL sub\nJP end\nsub: RET\nend: JP end");
+        let disassembly = disassemble(&rom, Platform::Chip8);
+
+        let labels: Vec<(usize, &str)> = disassembly
+            .labels()
+            .iter()
+            .map(|(&address, name)| (address, name.as_str()))
+            .collect();
+        assert_eq!(labels, [(0x204, "sub_204"), (0x206, "label_206")]);
+
+        let listing = disassembly.to_string();
+        assert!(listing.contains("CALL sub_204\n"));
+        assert!(listing.contains("JP label_206\n"));
+        assert!(listing.contains("sub_204:\n0x204"));
+    }
+
+    #[test]
+    fn computed_jump_stops_tracing() {
+        let rom = assemble("JP V0, 0x300\nCLS");
+        let disassembly = disassemble(&rom, Platform::Chip8);
+
+        assert_eq!(code_addresses(&disassembly), [0x200]);
+        assert!(disassembly
+            .to_string()
+            .ends_with("0x202  00 E0        db 0x00, 0xE0\n"));
+    }
+
+    #[test]
+    fn overlapping_instructions_keep_their_labels() {
+        // SE V0, 0; JP 0x205; CLS; then SKNP V0 at 0x205 made of the second byte of CLS and
+        // the first of LD I, 0x100.
+        let rom = Rom::from_bytes(vec![0x30, 0x00, 0x12, 0x05, 0x00, 0xE0, 0xA1, 0x00]);
+        let disassembly = disassemble(&rom, Platform::Chip8);
+
+        assert_eq!(
+            code_addresses(&disassembly),
+            [0x200, 0x202, 0x204, 0x205, 0x206]
+        );
+        assert_eq!(
+            disassembly.to_string(),
+            "\
+; platform: chip8
+0x200  30 00        SE V0, 0x00
+0x202  12 05        JP label_205
+0x204  00 E0        CLS
+label_205 = 0x205
+; 0x205  SKNP V0
+0x206  A1 00        LD I, 0x100
+"
+        );
+    }
+}
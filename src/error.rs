@@ -0,0 +1,134 @@
+use std::{error::Error, fmt, io};
+
+/// Everything that can go wrong while loading or running a program.
+///
+/// Runtime errors are raised before the faulting instruction has any effect, so the machine is
+/// left exactly as it was when the instruction was fetched.
+#[derive(Debug)]
+pub enum EmulatorError {
+    /// The instruction at `address` doesn't decode to anything.
+    InvalidOpcode { opcode: u16, address: usize },
+    /// A `CALL` at `address` with all 16 stack entries in use.
+    StackOverflow { address: usize },
+    /// A `RET` at `address` with an empty stack.
+    StackUnderflow { address: usize },
+    /// An access to `address`, which lies outside of memory.
+    MemoryOutOfRange { address: usize },
+    /// The ROM file couldn't be read.
+    RomIo(io::Error),
+    /// The ROM is `size` bytes long but only `max` fit in memory.
+    RomTooLarge { size: usize, max: usize },
+    /// A save state file couldn't be read or written.
+    StateIo(io::Error),
+    /// The data isn't a save state, or it is truncated or damaged.
+    CorruptState,
+    /// The save state is in format `version`, which this build can't read.
+    UnsupportedState { version: u16 },
+    /// A movie file couldn't be read or written.
+    MovieIo(io::Error),
+    /// The data isn't a movie, or it is truncated or damaged.
+    CorruptMovie,
+    /// The movie is in format `version`, which this build can't read.
+    UnsupportedMovie { version: u16 },
+    /// The movie was recorded with another ROM than the one it is played back with.
+    MovieRomMismatch,
+    /// The configuration file couldn't be read.
+    ConfigIo(io::Error),
+    /// The configuration file isn't valid; the message tells where and why.
+    InvalidConfig(String),
+    /// A ROM database file couldn't be read.
+    DatabaseIo(io::Error),
+    /// A ROM database file isn't valid; the message tells where and why.
+    InvalidDatabase(String),
+    /// A screenshot couldn't be written.
+    ScreenshotIo(io::Error),
+}
+
+impl fmt::Display for EmulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmulatorError::InvalidOpcode { opcode, address } => {
+                write!(f, "invalid opcode {:04X} at {:#05X}", opcode, address)
+            }
+            EmulatorError::StackOverflow { address } => {
+                write!(f, "stack overflow at {:#05X}", address)
+            }
+            EmulatorError::StackUnderflow { address } => {
+                write!(f, "stack underflow at {:#05X}", address)
+            }
+            EmulatorError::MemoryOutOfRange { address } => {
+                write!(f, "memory access out of range at {:#05X}", address)
+            }
+            EmulatorError::RomIo(err) => write!(f, "cannot read ROM: {}", err),
+            EmulatorError::RomTooLarge { size, max } => {
+                write!(f, "ROM is {} bytes, at most {} fit in memory", size, max)
+            }
+            EmulatorError::StateIo(err) => write!(f, "cannot access save state: {}", err),
+            EmulatorError::CorruptState => write!(f, "save state is damaged"),
+            EmulatorError::UnsupportedState { version } => write!(
+                f,
+                "save state format version {} is not supported (this build reads up to {})",
+                version,
+                crate::state::VERSION
+            ),
+            EmulatorError::MovieIo(err) => write!(f, "cannot access movie: {}", err),
+            EmulatorError::CorruptMovie => write!(f, "movie is damaged"),
+            EmulatorError::UnsupportedMovie { version } => write!(
+                f,
+                "movie format version {} is not supported (this build reads up to {})",
+                version,
+                crate::movie::VERSION
+            ),
+            EmulatorError::MovieRomMismatch => write!(f, "movie was recorded with another ROM"),
+            EmulatorError::ConfigIo(err) => write!(f, "cannot read configuration: {}", err),
+            EmulatorError::InvalidConfig(message) => {
+                write!(f, "invalid configuration: {}", message)
+            }
+            EmulatorError::DatabaseIo(err) => write!(f, "cannot read ROM database: {}", err),
+            EmulatorError::InvalidDatabase(message) => {
+                write!(f, "invalid ROM database: {}", message)
+            }
+            EmulatorError::ScreenshotIo(err) => write!(f, "cannot write screenshot: {}", err),
+        }
+    }
+}
+
+impl Error for EmulatorError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            EmulatorError::RomIo(err)
+            | EmulatorError::StateIo(err)
+            | EmulatorError::MovieIo(err)
+            | EmulatorError::ConfigIo(err)
+            | EmulatorError::DatabaseIo(err)
+            | EmulatorError::ScreenshotIo(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::{machine::Machine, render::Image, rom::Rom, screenshot, state};
+
+    #[test]
+    fn io_errors_say_what_was_accessed() {
+        let missing = env::temp_dir().join("chip8-missing-dir").join("file");
+
+        let err = Rom::open(&missing).err().unwrap();
+        assert!(matches!(err, EmulatorError::RomIo(_)));
+        assert!(err.to_string().starts_with("cannot read ROM: "));
+
+        let err = state::save_file(&Machine::new(), &missing).unwrap_err();
+        assert!(matches!(err, EmulatorError::StateIo(_)));
+        assert!(err.to_string().starts_with("cannot access save state: "));
+
+        let image = Image::default();
+        let err = screenshot::save_png(&image, &missing).unwrap_err();
+        assert!(matches!(err, EmulatorError::ScreenshotIo(_)));
+        assert!(err.source().is_some());
+    }
+}
@@ -0,0 +1,120 @@
+use sdl2::{
+    audio::{AudioCallback, AudioDevice, AudioSpecDesired},
+    Sdl,
+};
+
+use crate::audio::{Audio, Tone};
+
+const SAMPLE_RATE: i32 = 44100;
+
+/// Time the volume takes to ramp up or down when the buzzer starts or stops, in seconds.
+const RAMP_TIME: f32 = 0.005;
+
+/// Length in bits of the XO-CHIP audio pattern.
+const PATTERN_BITS: usize = 128;
+
+/// An XO-CHIP audio pattern and the rate it is played at, in bits per second.
+#[derive(Clone, Copy)]
+struct Pattern {
+    bits: [u8; 16],
+    rate: f32,
+}
+
+impl Pattern {
+    fn new(bits: [u8; 16], pitch: u8) -> Self {
+        Pattern {
+            bits,
+            rate: 4000.0 * 2f32.powf((pitch as f32 - 64.0) / 48.0),
+        }
+    }
+
+    fn sample(&self, phase: f32) -> f32 {
+        let bit = (phase * PATTERN_BITS as f32) as usize % PATTERN_BITS;
+
+        if self.bits[bit / 8] & (0x80 >> (bit % 8)) != 0 {
+            1.0
+        } else {
+            -1.0
+        }
+    }
+}
+
+/// Audio callback generating the buzzer sound.
+///
+/// The device keeps running while the buzzer is off, and the volume ramps up and down over
+/// [`RAMP_TIME`] instead of jumping, so starting and stopping doesn't click.
+struct Generator {
+    tone: Tone,
+    sample_rate: f32,
+    phase: f32,
+    gain: f32,
+    playing: bool,
+    pattern: Option<Pattern>,
+}
+
+impl AudioCallback for Generator {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        let target = if self.playing { 1.0 } else { 0.0 };
+        let ramp = 1.0 / (RAMP_TIME * self.sample_rate);
+
+        for sample in out.iter_mut() {
+            if self.gain < target {
+                self.gain = (self.gain + ramp).min(target)
+            } else if self.gain > target {
+                self.gain = (self.gain - ramp).max(target)
+            }
+
+            let (value, step) = match &self.pattern {
+                Some(pattern) => (
+                    pattern.sample(self.phase),
+                    pattern.rate / PATTERN_BITS as f32,
+                ),
+                None => (self.tone.waveform.sample(self.phase), self.tone.frequency),
+            };
+
+            *sample = value * self.gain * self.tone.volume;
+            self.phase = (self.phase + step / self.sample_rate).fract();
+        }
+    }
+}
+
+/// Sounds the buzzer while the sound timer is running.
+pub struct Beeper {
+    device: AudioDevice<Generator>,
+}
+
+impl Beeper {
+    pub fn new(sdl: &Sdl, tone: Tone) -> Result<Self, String> {
+        let spec = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1),
+            samples: None,
+        };
+
+        let device = sdl.audio()?.open_playback(None, &spec, |spec| Generator {
+            tone,
+            sample_rate: spec.freq as f32,
+            phase: 0.0,
+            gain: 0.0,
+            playing: false,
+            pattern: None,
+        })?;
+
+        device.resume();
+        Ok(Beeper { device })
+    }
+
+    /// Start or stop the buzzer to match the sound timer, or `forced` on, keeping it quiet
+    /// while `paused`. With `xo_chip`, the audio pattern loaded by the program, if it loaded
+    /// one, is played instead of the tone.
+    pub fn update(&mut self, audio: &Audio, forced: bool, xo_chip: bool, paused: bool) {
+        let mut generator = self.device.lock();
+        generator.playing = (audio.sound > 0 || forced) && !paused;
+        generator.pattern = audio
+            .pattern
+            .filter(|_| xo_chip)
+            .map(|bits| Pattern::new(bits, audio.pitch));
+    }
+}
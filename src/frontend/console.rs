@@ -0,0 +1,41 @@
+use std::{
+    io::{self, BufRead, Write},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+/// Lines typed on standard input, read on a background thread so the window stays
+/// responsive while waiting for them.
+pub struct Console {
+    lines: Receiver<String>,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        let (sender, lines) = mpsc::channel();
+
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Console { lines }
+    }
+
+    pub fn prompt(&self) {
+        print!("(chip8) ");
+        let _ = io::stdout().flush();
+    }
+
+    /// The next line typed, if there is one yet. Standard input being closed reads as `quit`.
+    pub fn read_line(&self) -> Option<String> {
+        match self.lines.try_recv() {
+            Ok(line) => Some(line),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some("quit".to_string()),
+        }
+    }
+}
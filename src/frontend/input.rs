@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+
+use sdl2::{
+    event::Event,
+    keyboard::{Keycode, Scancode},
+};
+
+use crate::{input::Keypad, keymap::Keymap};
+
+/// A [`Keymap`] resolved to the keys of the keyboard layout in use.
+///
+/// Names are looked up as what the keys type, so SDL video must be initialised.
+pub struct Bindings {
+    keys: HashMap<Scancode, usize>,
+}
+
+impl Bindings {
+    /// Resolve `keymap`, warning about names that aren't keys of this keyboard.
+    pub fn new(keymap: &Keymap) -> Self {
+        let mut keys = HashMap::new();
+
+        for key in 0..16 {
+            for name in keymap.keys(key) {
+                match Keycode::from_name(name).and_then(Scancode::from_keycode) {
+                    Some(scancode) => {
+                        keys.insert(scancode, key);
+                    }
+                    None => eprintln!("Unknown key `{}` for CHIP-8 key {:X}", name, key),
+                }
+            }
+        }
+
+        Bindings { keys }
+    }
+
+    /// Forward keyboard events to the CHIP-8 keypad.
+    pub fn listen(&self, keypad: &mut Keypad, event: &Event) {
+        match event {
+            Event::KeyDown { scancode, .. } => self.on_key(keypad, scancode, true),
+            Event::KeyUp { scancode, .. } => self.on_key(keypad, scancode, false),
+            _ => {}
+        }
+    }
+
+    fn on_key(&self, keypad: &mut Keypad, scancode: &Option<Scancode>, down: bool) {
+        if let Some(&key) = scancode.and_then(|scancode| self.keys.get(&scancode)) {
+            keypad.set(key, down)
+        }
+    }
+}
+
+/// Name of the key at `scancode` in the keyboard layout in use, as [`Bindings`] looks it up.
+pub fn key_name(scancode: Scancode) -> Option<String> {
+    Keycode::from_scancode(scancode)
+        .map(|keycode| keycode.name())
+        .filter(|name| !name.is_empty())
+}
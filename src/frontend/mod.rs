@@ -0,0 +1,536 @@
+//! SDL frontend: owns the window, the event loop and the wall clock, and drives a [`Machine`].
+
+use std::{
+    path::PathBuf,
+    thread,
+    time::{Duration, Instant},
+};
+
+use sdl2::{
+    event::{Event, WindowEvent},
+    keyboard::Scancode,
+    messagebox::{show_simple_message_box, MessageBoxFlag},
+    EventPump, Sdl,
+};
+
+use crate::{
+    debugger::Debugger,
+    error::EmulatorError,
+    input::Keypad,
+    keymap::{Keymap, KEYPAD_ORDER},
+    machine::{Machine, FRAME_RATE},
+    movie::{Movie, Player, Recorder},
+    palette::{Palette, PixelStyle},
+    rewind::{self, Rewind},
+    screenshot::Screenshots,
+    state,
+};
+
+use self::{audio::Beeper, console::Console, input::Bindings, video::Screen};
+
+pub use crate::audio::{Tone, Waveform};
+
+mod audio;
+mod console;
+mod input;
+mod video;
+
+/// User preferences for the frontend.
+#[derive(Clone, Debug)]
+pub struct Settings {
+    /// Title of the window.
+    pub title: String,
+    /// Sound of the buzzer.
+    pub tone: Tone,
+    /// Sound the buzzer while a key is held for `Fx0A`, as the COSMAC VIP did.
+    pub key_beep: bool,
+    /// Save states are written next to this path, usually the ROM, with its extension replaced
+    /// by `state0` to `state9` for each slot.
+    pub state_path: PathBuf,
+    /// Directory screenshots are saved in, named after the file at `state_path`.
+    pub screenshot_dir: PathBuf,
+    /// Memory budget of the rewind buffer in bytes.
+    pub rewind_memory: usize,
+    /// Frames between two rewind snapshots.
+    pub rewind_interval: u32,
+    /// Size in screen pixels of a lo-res pixel, setting the initial window size unless that
+    /// doesn't fit on the desktop.
+    pub scale: u32,
+    pub palette: Palette,
+    /// Shape of the pixels.
+    pub pixels: PixelStyle,
+    /// Start in fullscreen.
+    pub fullscreen: bool,
+    /// Present frames in step with the refresh of the monitor, to avoid tearing.
+    pub vsync: bool,
+    /// Keyboard keys of the keypad.
+    pub keymap: Keymap,
+}
+
+/// Default size in screen pixels of a lo-res pixel.
+pub const DEFAULT_SCALE: u32 = 20;
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            title: String::from("chip8"),
+            tone: Tone::default(),
+            key_beep: false,
+            state_path: PathBuf::from("chip8"),
+            screenshot_dir: PathBuf::from("."),
+            rewind_memory: rewind::DEFAULT_MEMORY,
+            rewind_interval: rewind::DEFAULT_INTERVAL,
+            scale: DEFAULT_SCALE,
+            palette: Palette::default(),
+            pixels: PixelStyle::default(),
+            fullscreen: false,
+            vsync: false,
+            keymap: Keymap::default(),
+        }
+    }
+}
+
+/// Hotkey breaking into the debugger.
+const DEBUG_KEY: Scancode = Scancode::F9;
+/// Hotkey saving the machine state to the current slot.
+const SAVE_STATE_KEY: Scancode = Scancode::F5;
+/// Hotkey restoring the machine state from the current slot.
+const LOAD_STATE_KEY: Scancode = Scancode::F8;
+/// Hotkeys selecting the previous and the next save state slot.
+const PREVIOUS_SLOT_KEY: Scancode = Scancode::F6;
+const NEXT_SLOT_KEY: Scancode = Scancode::F7;
+/// Hotkey rewinding the game for as long as it is held.
+const REWIND_KEY: Scancode = Scancode::Backspace;
+/// Hotkey binding the CHIP-8 keys one by one to the next keys pressed, and stopping early.
+const BIND_KEYS_KEY: Scancode = Scancode::F2;
+/// Key leaving a CHIP-8 key with the keys it has while binding.
+const SKIP_BINDING_KEY: Scancode = Scancode::Escape;
+/// Hotkey switching between a window and fullscreen.
+const FULLSCREEN_KEY: Scancode = Scancode::F11;
+/// Hotkey saving screenshots.
+const SCREENSHOT_KEY: Scancode = Scancode::F12;
+
+/// Number of save state slots.
+const SLOT_COUNT: u8 = 10;
+
+/// Window, keyboard, speaker and clock for one running [`Machine`].
+pub struct Frontend {
+    screen: Screen,
+    beeper: Option<Beeper>,
+    scheduler: Scheduler,
+    events: EventPump,
+
+    debugger: Debugger,
+    console: Option<Console>,
+
+    state_path: PathBuf,
+    slot: u8,
+    rewind: Rewind,
+    screenshots: Screenshots,
+
+    key_beep: bool,
+    title: String,
+    keymap: Keymap,
+    bindings: Bindings,
+    /// Position in [`KEYPAD_ORDER`] of the CHIP-8 key being bound, while binding.
+    binding: Option<usize>,
+
+    movie: Option<MovieMode>,
+    /// Keys really held, while a movie decides what the machine sees.
+    keys: Keypad,
+}
+
+/// A movie being recorded or played back.
+enum MovieMode {
+    Recording(Recorder),
+    Playing(Player),
+}
+
+/// Paces frames at [`FRAME_RATE`] against the wall clock.
+///
+/// Deadlines are kept on an absolute schedule rather than measured from the last frame, so
+/// the average rate stays exact however the individual frames are late.
+struct Scheduler {
+    frame_time: Duration,
+    next_frame: Instant,
+}
+
+impl Scheduler {
+    /// Most frames run back to back to catch up after a stall, before giving up on them.
+    const MAX_CATCH_UP: u32 = 4;
+
+    fn new() -> Self {
+        Scheduler {
+            frame_time: Duration::from_secs_f32(1.0 / FRAME_RATE),
+            next_frame: Instant::now(),
+        }
+    }
+
+    /// Number of frames due since the last call.
+    fn due(&mut self) -> u32 {
+        let now = Instant::now();
+        let mut frames = 0;
+
+        while self.next_frame <= now {
+            self.next_frame += self.frame_time;
+            frames += 1;
+
+            if frames == Self::MAX_CATCH_UP {
+                self.next_frame = now + self.frame_time;
+                break;
+            }
+        }
+
+        frames
+    }
+
+    /// Sleep until the next frame is due.
+    fn wait(&self) {
+        let now = Instant::now();
+
+        if self.next_frame > now {
+            thread::sleep(self.next_frame - now)
+        }
+    }
+}
+
+impl Frontend {
+    pub fn new(sdl: &Sdl, settings: &Settings) -> Self {
+        let beeper = match Beeper::new(sdl, settings.tone) {
+            Ok(beeper) => Some(beeper),
+            Err(err) => {
+                eprintln!("Sound disabled: {}", err);
+                None
+            }
+        };
+
+        // Key names are resolved with the keyboard layout SDL video knows about.
+        let screen = Screen::new(sdl, settings);
+
+        Frontend {
+            screen,
+            beeper,
+            scheduler: Scheduler::new(),
+            events: sdl.event_pump().unwrap(),
+            debugger: Debugger::new(),
+            console: None,
+            state_path: settings.state_path.clone(),
+            slot: 0,
+            rewind: Rewind::new(settings.rewind_memory, settings.rewind_interval),
+            screenshots: Screenshots::new(settings.screenshot_dir.clone(), &settings.state_path),
+            key_beep: settings.key_beep,
+            title: settings.title.clone(),
+            keymap: settings.keymap.clone(),
+            bindings: Bindings::new(&settings.keymap),
+            binding: None,
+            movie: None,
+            keys: Keypad::new(),
+        }
+    }
+
+    /// Record the keys of every frame from now on, until [`Frontend::take_recording`].
+    pub fn record(&mut self, recorder: Recorder) {
+        self.movie = Some(MovieMode::Recording(recorder));
+    }
+
+    /// Play `player` back, ignoring the keyboard until it is finished.
+    pub fn play(&mut self, player: Player) {
+        self.movie = Some(MovieMode::Playing(player));
+    }
+
+    /// Stop recording and return the movie, if one is being recorded.
+    pub fn take_recording(&mut self) -> Option<Movie> {
+        match self.movie.take() {
+            Some(MovieMode::Recording(recorder)) => Some(recorder.finish()),
+            movie => {
+                self.movie = movie;
+                None
+            }
+        }
+    }
+
+    /// Set the keypad for the next frame from the movie, if there is one.
+    fn before_frame(&mut self, machine: &mut Machine) {
+        match &mut self.movie {
+            Some(MovieMode::Recording(recorder)) => {
+                recorder.before_frame(&mut machine.keypad, self.keys.pressed)
+            }
+            Some(MovieMode::Playing(player)) => player.before_frame(&mut machine.keypad),
+            None => {}
+        }
+    }
+
+    fn after_frame(&mut self, machine: &mut Machine) {
+        match &mut self.movie {
+            Some(MovieMode::Recording(recorder)) => recorder.after_frame(machine),
+            Some(MovieMode::Playing(player)) => {
+                if !player.after_frame(machine) {
+                    eprintln!("Movie desynced at frame {}", player.frame());
+                }
+
+                if player.finished() {
+                    println!("Movie finished after {} frames", player.frame());
+                    machine.keypad.pressed = self.keys.pressed;
+                    self.movie = None;
+                }
+            }
+            None => self.rewind.record_frame(machine),
+        }
+    }
+
+    /// File of the current save state slot.
+    fn slot_path(&self) -> PathBuf {
+        self.state_path.with_extension(format!("state{}", self.slot))
+    }
+
+    /// Save `machine` to the current slot, reporting the outcome on the terminal.
+    fn save_state(&self, machine: &Machine) {
+        let path = self.slot_path();
+
+        match state::save_file(machine, &path) {
+            Ok(()) => println!("Saved slot {} to {}", self.slot, path.display()),
+            Err(err) => eprintln!("Cannot save slot {}: {}", self.slot, err),
+        }
+    }
+
+    /// Save the display as shown, as it is and at the scale of the window, reporting the
+    /// outcome on the terminal.
+    fn screenshot(&mut self, machine: &Machine) {
+        let scale = self.screen.scale(&machine.display) as usize;
+
+        match self
+            .screenshots
+            .take(self.screen.renderer(), &machine.display, scale)
+        {
+            Ok(paths) => {
+                for path in paths {
+                    println!("Saved screenshot {}", path.display());
+                }
+            }
+            Err(err) => eprintln!("Cannot save screenshot: {}", err),
+        }
+    }
+
+    /// Restore `machine` from the current slot, reporting the outcome on the terminal.
+    fn load_state(&self, machine: &mut Machine) {
+        if self.movie.is_some() {
+            eprintln!("Cannot load a state while a movie is recording or playing");
+            return;
+        }
+
+        let path = self.slot_path();
+
+        match state::load_file(machine, &path) {
+            Ok(()) => println!("Loaded slot {} from {}", self.slot, path.display()),
+            Err(err) => eprintln!("Cannot load slot {}: {}", self.slot, err),
+        }
+    }
+
+    /// Move to another save state slot, `offset` slots away.
+    fn select_slot(&mut self, offset: i8) {
+        self.slot = (self.slot as i8 + offset).rem_euclid(SLOT_COUNT as i8) as u8;
+        println!("Save state slot {}", self.slot);
+    }
+
+    /// Pause the machine and bind the CHIP-8 keys one by one, in keypad order, to the next
+    /// keys pressed.
+    fn start_binding(&mut self, machine: &mut Machine) {
+        // Releases are not seen while binding, so forget what is held rather than have keys
+        // stuck down afterwards.
+        self.keys.pressed = [false; 16];
+        if self.movie.is_none() {
+            machine.keypad.pressed = [false; 16];
+        }
+
+        println!(
+            "Binding keys: press the key for each CHIP-8 key, {} to keep its keys, {} to stop",
+            SKIP_BINDING_KEY.name(),
+            BIND_KEYS_KEY.name()
+        );
+
+        self.binding = Some(0);
+        self.prompt_binding(0);
+    }
+
+    fn prompt_binding(&mut self, position: usize) {
+        let key = KEYPAD_ORDER[position];
+        println!("CHIP-8 key {:X}, now {}", key, self.keymap.keys(key).join(", "));
+        self.screen.set_title(&format!("Press the key for CHIP-8 key {:X}", key));
+    }
+
+    /// Bind the CHIP-8 key being bound to the key at `scancode`, and move to the next one.
+    fn bind_key(&mut self, position: usize, scancode: Scancode) {
+        let key = KEYPAD_ORDER[position];
+
+        match scancode {
+            BIND_KEYS_KEY => return self.finish_binding(),
+            SKIP_BINDING_KEY => {}
+            _ => match input::key_name(scancode) {
+                Some(name) => self.keymap.bind(key, vec![name]),
+                None => {
+                    eprintln!("That key can't be bound, press another one");
+                    return;
+                }
+            },
+        }
+
+        if position + 1 < KEYPAD_ORDER.len() {
+            self.binding = Some(position + 1);
+            self.prompt_binding(position + 1);
+        } else {
+            self.finish_binding();
+        }
+    }
+
+    fn finish_binding(&mut self) {
+        self.binding = None;
+        self.bindings = Bindings::new(&self.keymap);
+        self.screen.set_title(&self.title);
+
+        println!("Keys bound. To keep them, add this to the configuration:");
+        println!("keymap = {}", self.keymap);
+    }
+
+    /// Stop the machine and take commands from standard input until told to continue.
+    pub fn break_into_debugger(&mut self, machine: &Machine) {
+        if self.debugger.paused() {
+            return;
+        }
+
+        self.debugger.pause(machine);
+        self.console.get_or_insert_with(Console::new).prompt();
+    }
+
+    /// Wait for the next frame, pump events and run the machine for every frame due, then
+    /// present the display. Returns `false` once the window is closed or the program exits.
+    pub fn tick(&mut self, machine: &mut Machine) -> Result<bool, EmulatorError> {
+        self.scheduler.wait();
+
+        let events: Vec<Event> = self.events.poll_iter().collect();
+
+        for event in events {
+            match event {
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(..) | WindowEvent::Exposed,
+                    ..
+                } => self.screen.invalidate(),
+                Event::KeyDown {
+                    scancode: Some(FULLSCREEN_KEY),
+                    repeat: false,
+                    ..
+                } => {
+                    self.screen.toggle_fullscreen();
+                    continue;
+                }
+                _ => {}
+            }
+
+            if let Some(position) = self.binding {
+                match event {
+                    Event::Quit { .. } => return Ok(false),
+                    Event::KeyDown {
+                        scancode: Some(scancode),
+                        repeat: false,
+                        ..
+                    } => self.bind_key(position, scancode),
+                    _ => {}
+                }
+
+                continue;
+            }
+
+            if self.movie.is_some() {
+                self.bindings.listen(&mut self.keys, &event);
+            } else {
+                self.bindings.listen(&mut machine.keypad, &event);
+            }
+
+            match event {
+                Event::Quit { .. } => return Ok(false),
+                Event::KeyDown {
+                    scancode: Some(scancode),
+                    repeat: false,
+                    ..
+                } => match scancode {
+                    DEBUG_KEY => self.break_into_debugger(machine),
+                    SAVE_STATE_KEY => self.save_state(machine),
+                    LOAD_STATE_KEY => self.load_state(machine),
+                    PREVIOUS_SLOT_KEY => self.select_slot(-1),
+                    NEXT_SLOT_KEY => self.select_slot(1),
+                    BIND_KEYS_KEY => self.start_binding(machine),
+                    SCREENSHOT_KEY => self.screenshot(machine),
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+
+        if let Some(console) = &self.console {
+            if let Some(line) = console.read_line() {
+                self.debugger.refuse_stepping(self.movie.is_some());
+
+                if !self.debugger.command(machine, &line)? {
+                    return Ok(false);
+                }
+
+                if self.debugger.paused() {
+                    console.prompt();
+                }
+            }
+        }
+
+        let was_paused = self.debugger.paused();
+        let binding = self.binding.is_some();
+        let rewinding = !was_paused
+            && !binding
+            && self.movie.is_none()
+            && self
+                .events
+                .keyboard_state()
+                .is_scancode_pressed(REWIND_KEY);
+
+        for _ in 0..self.scheduler.due() {
+            if rewinding {
+                // Keys keep the state they really are in, not the one they were in back then.
+                let pressed = machine.keypad.pressed;
+                self.rewind.rewind_frame(machine);
+                machine.keypad.pressed = pressed;
+            } else if !self.debugger.paused() && !binding {
+                self.before_frame(machine);
+
+                if self.debugger.run_frame(machine)? {
+                    self.after_frame(machine);
+                }
+            }
+        }
+
+        if let Some(console) = &self.console {
+            if !was_paused && self.debugger.paused() {
+                console.prompt();
+            }
+        }
+
+        self.screen.draw(&mut machine.display);
+
+        if let Some(beeper) = &mut self.beeper {
+            beeper.update(
+                &machine.audio,
+                self.key_beep && machine.key_held(),
+                machine.platform().xo_chip(),
+                self.debugger.paused() || binding || rewinding,
+            );
+        }
+
+        Ok(!machine.exited())
+    }
+
+    /// Tell the user the machine stopped because of `err`.
+    pub fn show_error(&self, err: &EmulatorError) {
+        let _ = show_simple_message_box(
+            MessageBoxFlag::ERROR,
+            "chip8",
+            &err.to_string(),
+            self.screen.window(),
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use std::mem;
+
+use sdl2::{
+    pixels::{Color, PixelFormatEnum},
+    rect::Rect,
+    render::{Canvas, Texture, TextureCreator},
+    video::{FullscreenType, Window, WindowContext},
+    Sdl,
+};
+
+use crate::{
+    palette::PixelStyle,
+    render::Renderer,
+    video::{Display, DISPLAY_HEIGHT, DISPLAY_WIDTH},
+};
+
+use super::Settings;
+
+/// Colour of the bars around the display when the window doesn't have its aspect ratio.
+const LETTERBOX: Color = Color::BLACK;
+
+/// Draws a [`Display`] framebuffer into an SDL window, scaled up by the largest whole number
+/// that fits and centred.
+pub struct Screen {
+    canvas: Canvas<Window>,
+    /// Whether to draw even if the display is unchanged, as the window was resized.
+    redraw: bool,
+    renderer: Renderer,
+    /// Creates textures for the canvas. They aren't tied to it by a lifetime, so that one can
+    /// be kept from one frame to the next, and are freed along with the canvas.
+    textures: TextureCreator<WindowContext>,
+    /// The texture the display is uploaded to, with its size.
+    texture: Option<((u32, u32), Texture)>,
+}
+
+impl Screen {
+    pub fn new(sdl: &Sdl, settings: &Settings) -> Self {
+        let video = sdl.video().unwrap();
+
+        // Shrink the window to fit on the desktop, with some room to spare.
+        let scale = match video.desktop_display_mode(0) {
+            Ok(mode) => {
+                let fit = (mode.w as u32 * 9 / 10 / DISPLAY_WIDTH as u32)
+                    .min(mode.h as u32 * 9 / 10 / DISPLAY_HEIGHT as u32);
+                settings.scale.min(fit).max(1)
+            }
+            Err(_) => settings.scale,
+        };
+
+        let mut window = video.window(
+            &settings.title,
+            DISPLAY_WIDTH as u32 * scale,
+            DISPLAY_HEIGHT as u32 * scale,
+        );
+        window.position_centered().resizable().opengl();
+
+        if settings.fullscreen {
+            window.fullscreen_desktop();
+        }
+
+        let mut window = window.build().unwrap();
+        let _ = window.set_minimum_size(DISPLAY_WIDTH as u32, DISPLAY_HEIGHT as u32);
+
+        let mut canvas = window.into_canvas();
+
+        if settings.vsync {
+            canvas = canvas.present_vsync();
+        }
+
+        let mut canvas = canvas.build().unwrap();
+        canvas.set_draw_color(LETTERBOX);
+        canvas.clear();
+        canvas.present();
+
+        Screen {
+            textures: canvas.texture_creator(),
+            canvas,
+            redraw: true,
+            renderer: Renderer::new(settings.palette, settings.pixels),
+            texture: None,
+        }
+    }
+
+    pub fn window(&self) -> &Window {
+        self.canvas.window()
+    }
+
+    pub fn set_title(&mut self, title: &str) {
+        let _ = self.canvas.window_mut().set_title(title);
+    }
+
+    /// Draw the display again on the next [`Screen::draw`], after the window was resized or
+    /// uncovered.
+    pub fn invalidate(&mut self) {
+        self.redraw = true;
+    }
+
+    /// Switch between a window and fullscreen at the desktop resolution.
+    pub fn toggle_fullscreen(&mut self) {
+        let window = self.canvas.window_mut();
+
+        let fullscreen = match window.fullscreen_state() {
+            FullscreenType::Off => FullscreenType::Desktop,
+            _ => FullscreenType::Off,
+        };
+
+        if let Err(err) = window.set_fullscreen(fullscreen) {
+            eprintln!("Can't toggle fullscreen: {}", err);
+        }
+
+        self.redraw = true;
+    }
+
+    /// Draws the display for the window and for screenshots.
+    pub fn renderer(&mut self) -> &mut Renderer {
+        &mut self.renderer
+    }
+
+    /// Screen pixels per pixel of `display` in the window: the largest whole number that fits.
+    pub fn scale(&self, display: &Display) -> u32 {
+        let (width, height) = self.canvas.output_size().unwrap();
+
+        (width / display.width() as u32)
+            .min(height / display.height() as u32)
+            .max(1)
+    }
+
+    /// Draw `display` if it changed, or the window needs it, and present it.
+    ///
+    /// The image is rendered in software and uploaded as a single texture, which the GPU
+    /// scales up to fill the window.
+    pub fn draw(&mut self, display: &mut Display) {
+        let redraw = mem::take(&mut self.redraw);
+
+        if !display.consume_update() && !redraw {
+            return;
+        }
+
+        let (width, height) = self.canvas.output_size().unwrap();
+        let scale = self.scale(display);
+
+        // Centre the display, with bars on the sides that are too long for its aspect ratio.
+        let area = Rect::new(
+            ((width as i32 - (display.width() as u32 * scale) as i32) / 2).max(0),
+            ((height as i32 - (display.height() as u32 * scale) as i32) / 2).max(0),
+            display.width() as u32 * scale,
+            display.height() as u32 * scale,
+        );
+
+        // Squares scale up as they are; other shapes are drawn at the size they are shown at.
+        let image = match self.renderer.pixels {
+            PixelStyle::Square => self.renderer.render(display, 1),
+            _ => self.renderer.render(display, scale as usize),
+        };
+
+        let size = (image.width as u32, image.height as u32);
+
+        if self.texture.as_ref().map(|texture| texture.0) != Some(size) {
+            let texture = self
+                .textures
+                .create_texture_streaming(PixelFormatEnum::RGB24, size.0, size.1)
+                .unwrap();
+
+            if let Some((_, old)) = self.texture.replace((size, texture)) {
+                // SAFETY: the texture was made for this canvas, which is still alive, and
+                // nothing refers to it anymore.
+                unsafe { old.destroy() }
+            }
+        }
+
+        let texture = &mut self.texture.as_mut().unwrap().1;
+        let _ = texture.update(None, &image.rgb, image.width * 3);
+
+        self.canvas.set_draw_color(LETTERBOX);
+        self.canvas.clear();
+        let _ = self.canvas.copy(texture, None, area);
+        self.canvas.present();
+    }
+}
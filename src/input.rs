@@ -1,76 +1,64 @@
-use std::collections::HashMap;
-
-use once_cell::sync::Lazy;
-use sdl2::{event::Event, keyboard::Scancode};
-
-/// ```text
-/// 11 22 33 C4
-/// 4Q 5W 6E DR
-/// 7A 8S 9D EF
-/// AZ 0X BC FV
-/// ```
-const KEY_MAPPING: Lazy<HashMap<Scancode, usize>> = Lazy::new(|| {
-    let mut map: HashMap<Scancode, usize> = HashMap::new();
-
-    map.insert(Scancode::Num1, 0x1);
-    map.insert(Scancode::Num2, 0x2);
-    map.insert(Scancode::Num3, 0x3);
-    map.insert(Scancode::Num4, 0xC);
-
-    map.insert(Scancode::Q, 0x4);
-    map.insert(Scancode::W, 0x5);
-    map.insert(Scancode::E, 0x6);
-    map.insert(Scancode::R, 0xD);
-
-    map.insert(Scancode::A, 0x7);
-    map.insert(Scancode::S, 0x8);
-    map.insert(Scancode::D, 0x9);
-    map.insert(Scancode::F, 0xE);
-
-    map.insert(Scancode::Z, 0xA);
-    map.insert(Scancode::X, 0x0);
-    map.insert(Scancode::C, 0xB);
-    map.insert(Scancode::V, 0xF);
-
-    map
-});
+use crate::{
+    error::EmulatorError,
+    state::{Reader, Writer},
+};
 
+/// State of the 16-key hexadecimal keypad.
 pub struct Keypad {
+    /// Whether each key `0x0..=0xF` is held down.
     pub pressed: [bool; 16],
-    last_released: Option<u8>,
+    last_pressed: Option<u8>,
+}
+
+impl Default for Keypad {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Keypad {
     pub fn new() -> Self {
         Keypad {
             pressed: [false; 16],
-            last_released: None,
+            last_pressed: None,
         }
     }
 
-    pub fn listen(&mut self, event: &Event) {
-        match event {
-            Event::KeyDown { scancode, .. } => self.on_key(scancode, true),
-            Event::KeyUp { scancode, .. } => self.on_key(scancode, false),
-            _ => {}
+    /// Update the state of CHIP-8 key `key` (`0x0..=0xF`).
+    ///
+    /// Setting a key that is already down again, as key repeat does, isn't a new press.
+    pub fn set(&mut self, key: usize, down: bool) {
+        if down && !self.pressed[key] {
+            self.last_pressed = Some(key as u8)
         }
+
+        self.pressed[key] = down;
     }
 
-    pub fn consume_last_released(&mut self) -> Option<u8> {
-        let last_released = self.last_released;
-        self.last_released = None;
-        last_released
+    pub(crate) fn save(&self, writer: &mut Writer) {
+        let pressed = (0..16).fold(0, |bits, key| bits | (self.pressed[key] as u16) << key);
+        writer.u16(pressed);
+        writer.u8(self.last_pressed.unwrap_or(u8::MAX));
     }
 
-    fn on_key(&mut self, scancode: &Option<Scancode>, down: bool) {
-        if let Some(scancode) = scancode {
-            if KEY_MAPPING.contains_key(scancode) {
-                self.pressed[KEY_MAPPING[scancode]] = down;
+    pub(crate) fn load(reader: &mut Reader) -> Result<Self, EmulatorError> {
+        let pressed = reader.u16()?;
 
-                if !down {
-                    self.last_released = Some(KEY_MAPPING[scancode] as u8)
-                }
-            }
-        }
+        let last_pressed = match reader.u8()? {
+            u8::MAX => None,
+            key @ 0..=0xF => Some(key),
+            _ => return Err(EmulatorError::CorruptState),
+        };
+
+        Ok(Keypad {
+            pressed: std::array::from_fn(|key| pressed & 1 << key != 0),
+            // Version 2 stored the last key released instead, which nothing waits for anymore.
+            last_pressed: last_pressed.filter(|_| reader.version >= 3),
+        })
+    }
+
+    /// The key pressed most recently, if any, clearing it.
+    pub fn consume_last_pressed(&mut self) -> Option<u8> {
+        self.last_pressed.take()
     }
 }
@@ -0,0 +1,348 @@
+use std::fmt;
+
+use crate::platform::Platform;
+
+/// A decoded instruction.
+///
+/// This is the single decoder shared by the interpreter, the debugger, the disassembler and
+/// the assembler, so they can never disagree on what an opcode means. Registers are indices
+/// into V0 to VF; the mnemonics are the ones documented on the interpreter's instruction
+/// handlers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    /// 0nnn - SYS addr
+    Sys(u16),
+    /// 00Cn - SCD nibble
+    ScrollDown(u8),
+    /// 00E0 - CLS
+    Clear,
+    /// 00EE - RET
+    Return,
+    /// 00FB - SCR
+    ScrollRight,
+    /// 00FC - SCL
+    ScrollLeft,
+    /// 00FD - EXIT
+    Exit,
+    /// 00FE - LOW
+    Low,
+    /// 00FF - HIGH
+    High,
+    /// 1nnn - JP addr
+    Jump(u16),
+    /// 2nnn - CALL addr
+    Call(u16),
+    /// 3xkk - SE Vx, byte
+    SkipEqualByte(usize, u8),
+    /// 4xkk - SNE Vx, byte
+    SkipNotEqualByte(usize, u8),
+    /// 5xy0 - SE Vx, Vy
+    SkipEqual(usize, usize),
+    /// 5xy2 - LD [I], Vx - Vy
+    StoreRange(usize, usize),
+    /// 5xy3 - LD Vx - Vy, [I]
+    LoadRange(usize, usize),
+    /// 6xkk - LD Vx, byte
+    LoadByte(usize, u8),
+    /// 7xkk - ADD Vx, byte
+    AddByte(usize, u8),
+    /// 8xy0 - LD Vx, Vy
+    Load(usize, usize),
+    /// 8xy1 - OR Vx, Vy
+    Or(usize, usize),
+    /// 8xy2 - AND Vx, Vy
+    And(usize, usize),
+    /// 8xy3 - XOR Vx, Vy
+    Xor(usize, usize),
+    /// 8xy4 - ADD Vx, Vy
+    Add(usize, usize),
+    /// 8xy5 - SUB Vx, Vy
+    Sub(usize, usize),
+    /// 8xy6 - SHR Vx, Vy
+    ShiftRight(usize, usize),
+    /// 8xy7 - SUBN Vx, Vy
+    SubNegated(usize, usize),
+    /// 8xyE - SHL Vx, Vy
+    ShiftLeft(usize, usize),
+    /// 9xy0 - SNE Vx, Vy
+    SkipNotEqual(usize, usize),
+    /// Annn - LD I, addr
+    LoadI(u16),
+    /// Bnnn - JP V0, addr
+    JumpOffset(u16),
+    /// Cxkk - RND Vx, byte
+    Random(usize, u8),
+    /// Dxyn - DRW Vx, Vy, nibble
+    Draw(usize, usize, u8),
+    /// Ex9E - SKP Vx
+    SkipPressed(usize),
+    /// ExA1 - SKNP Vx
+    SkipNotPressed(usize),
+    /// F000 nnnn - LD I, long addr
+    LoadLongI(u16),
+    /// Fn01 - PLANE n
+    Plane(u8),
+    /// F002 - AUDIO
+    Audio,
+    /// Fx07 - LD Vx, DT
+    LoadDelay(usize),
+    /// Fx0A - LD Vx, K
+    WaitKey(usize),
+    /// Fx15 - LD DT, Vx
+    SetDelay(usize),
+    /// Fx18 - LD ST, Vx
+    SetSound(usize),
+    /// Fx1E - ADD I, Vx
+    AddI(usize),
+    /// Fx29 - LD F, Vx
+    Font(usize),
+    /// Fx30 - LD HF, Vx
+    BigFont(usize),
+    /// Fx33 - LD B, Vx
+    Bcd(usize),
+    /// Fx3A - PITCH Vx
+    Pitch(usize),
+    /// Fx55 - LD [I], Vx
+    Store(usize),
+    /// Fx65 - LD Vx, [I]
+    Restore(usize),
+    /// Fx75 - LD R, Vx
+    StoreFlags(usize),
+    /// Fx85 - LD Vx, R
+    RestoreFlags(usize),
+}
+
+impl Instruction {
+    /// Decode `opcode`, with `next` the word following it, as `platform` would.
+    pub fn decode(opcode: u16, next: u16, platform: Platform) -> Option<Instruction> {
+        let nibble = (
+            ((opcode & 0xF000) >> 12) as u8,
+            ((opcode & 0x0F00) >> 8) as u8,
+            ((opcode & 0x00F0) >> 4) as u8,
+            (opcode & 0x000F) as u8,
+        );
+
+        let x = nibble.1 as usize;
+        let y = nibble.2 as usize;
+
+        let n = nibble.3;
+        let nn = (opcode & 0x00FF) as u8;
+        let nnn = opcode & 0x0FFF;
+
+        let schip = platform.super_chip();
+        let xo = platform.xo_chip();
+
+        use Instruction::*;
+
+        let instruction = match nibble {
+            (0x0, 0x0, 0xC, _) if schip => ScrollDown(n),
+            (0x0, 0x0, 0xE, 0x0) => Clear,
+            (0x0, 0x0, 0xE, 0xE) => Return,
+            (0x0, 0x0, 0xF, 0xB) if schip => ScrollRight,
+            (0x0, 0x0, 0xF, 0xC) if schip => ScrollLeft,
+            (0x0, 0x0, 0xF, 0xD) if schip => Exit,
+            (0x0, 0x0, 0xF, 0xE) if schip => Low,
+            (0x0, 0x0, 0xF, 0xF) if schip => High,
+            (0x0, _, _, _) => Sys(nnn),
+            (0x1, _, _, _) => Jump(nnn),
+            (0x2, _, _, _) => Call(nnn),
+            (0x3, _, _, _) => SkipEqualByte(x, nn),
+            (0x4, _, _, _) => SkipNotEqualByte(x, nn),
+            (0x5, _, _, 0x0) => SkipEqual(x, y),
+            (0x5, _, _, 0x2) if xo => StoreRange(x, y),
+            (0x5, _, _, 0x3) if xo => LoadRange(x, y),
+            (0x6, _, _, _) => LoadByte(x, nn),
+            (0x7, _, _, _) => AddByte(x, nn),
+            (0x8, _, _, 0x0) => Load(x, y),
+            (0x8, _, _, 0x1) => Or(x, y),
+            (0x8, _, _, 0x2) => And(x, y),
+            (0x8, _, _, 0x3) => Xor(x, y),
+            (0x8, _, _, 0x4) => Add(x, y),
+            (0x8, _, _, 0x5) => Sub(x, y),
+            (0x8, _, _, 0x6) => ShiftRight(x, y),
+            (0x8, _, _, 0x7) => SubNegated(x, y),
+            (0x8, _, _, 0xE) => ShiftLeft(x, y),
+            (0x9, _, _, 0x0) => SkipNotEqual(x, y),
+            (0xA, _, _, _) => LoadI(nnn),
+            (0xB, _, _, _) => JumpOffset(nnn),
+            (0xC, _, _, _) => Random(x, nn),
+            (0xD, _, _, _) => Draw(x, y, n),
+            (0xE, _, 0x9, 0xE) => SkipPressed(x),
+            (0xE, _, 0xA, 0x1) => SkipNotPressed(x),
+            (0xF, 0x0, 0x0, 0x0) if xo => LoadLongI(next),
+            (0xF, _, 0x0, 0x1) if xo => Plane(x as u8),
+            (0xF, 0x0, 0x0, 0x2) if xo => Audio,
+            (0xF, _, 0x0, 0x7) => LoadDelay(x),
+            (0xF, _, 0x0, 0xA) => WaitKey(x),
+            (0xF, _, 0x1, 0x5) => SetDelay(x),
+            (0xF, _, 0x1, 0x8) => SetSound(x),
+            (0xF, _, 0x1, 0xE) => AddI(x),
+            (0xF, _, 0x2, 0x9) => Font(x),
+            (0xF, _, 0x3, 0x0) if schip => BigFont(x),
+            (0xF, _, 0x3, 0x3) => Bcd(x),
+            (0xF, _, 0x3, 0xA) if xo => Pitch(x),
+            (0xF, _, 0x5, 0x5) => Store(x),
+            (0xF, _, 0x6, 0x5) => Restore(x),
+            (0xF, _, 0x7, 0x5) if schip => StoreFlags(x),
+            (0xF, _, 0x8, 0x5) if schip => RestoreFlags(x),
+            _ => return None,
+        };
+
+        Some(instruction)
+    }
+
+    /// The opcode, followed by the extra word of `F000 nnnn`.
+    pub fn encode(&self) -> (u16, Option<u16>) {
+        use Instruction::*;
+
+        let xy = |base: u16, x: usize, y: usize| base | (x as u16) << 8 | (y as u16) << 4;
+        let xkk = |base: u16, x: usize, kk: u8| base | (x as u16) << 8 | kk as u16;
+        let fx = |low: u16, x: usize| 0xF000 | (x as u16) << 8 | low;
+
+        let opcode = match *self {
+            Sys(nnn) => nnn & 0x0FFF,
+            ScrollDown(n) => 0x00C0 | (n & 0xF) as u16,
+            Clear => 0x00E0,
+            Return => 0x00EE,
+            ScrollRight => 0x00FB,
+            ScrollLeft => 0x00FC,
+            Exit => 0x00FD,
+            Low => 0x00FE,
+            High => 0x00FF,
+            Jump(nnn) => 0x1000 | (nnn & 0x0FFF),
+            Call(nnn) => 0x2000 | (nnn & 0x0FFF),
+            SkipEqualByte(x, kk) => xkk(0x3000, x, kk),
+            SkipNotEqualByte(x, kk) => xkk(0x4000, x, kk),
+            SkipEqual(x, y) => xy(0x5000, x, y),
+            StoreRange(x, y) => xy(0x5002, x, y),
+            LoadRange(x, y) => xy(0x5003, x, y),
+            LoadByte(x, kk) => xkk(0x6000, x, kk),
+            AddByte(x, kk) => xkk(0x7000, x, kk),
+            Load(x, y) => xy(0x8000, x, y),
+            Or(x, y) => xy(0x8001, x, y),
+            And(x, y) => xy(0x8002, x, y),
+            Xor(x, y) => xy(0x8003, x, y),
+            Add(x, y) => xy(0x8004, x, y),
+            Sub(x, y) => xy(0x8005, x, y),
+            ShiftRight(x, y) => xy(0x8006, x, y),
+            SubNegated(x, y) => xy(0x8007, x, y),
+            ShiftLeft(x, y) => xy(0x800E, x, y),
+            SkipNotEqual(x, y) => xy(0x9000, x, y),
+            LoadI(nnn) => 0xA000 | (nnn & 0x0FFF),
+            JumpOffset(nnn) => 0xB000 | (nnn & 0x0FFF),
+            Random(x, kk) => xkk(0xC000, x, kk),
+            Draw(x, y, n) => xy(0xD000, x, y) | (n & 0xF) as u16,
+            SkipPressed(x) => xkk(0xE000, x, 0x9E),
+            SkipNotPressed(x) => xkk(0xE000, x, 0xA1),
+            LoadLongI(nnnn) => return (0xF000, Some(nnnn)),
+            Plane(n) => fx(0x01, (n & 0xF) as usize),
+            Audio => 0xF002,
+            LoadDelay(x) => fx(0x07, x),
+            WaitKey(x) => fx(0x0A, x),
+            SetDelay(x) => fx(0x15, x),
+            SetSound(x) => fx(0x18, x),
+            AddI(x) => fx(0x1E, x),
+            Font(x) => fx(0x29, x),
+            BigFont(x) => fx(0x30, x),
+            Bcd(x) => fx(0x33, x),
+            Pitch(x) => fx(0x3A, x),
+            Store(x) => fx(0x55, x),
+            Restore(x) => fx(0x65, x),
+            StoreFlags(x) => fx(0x75, x),
+            RestoreFlags(x) => fx(0x85, x),
+        };
+
+        (opcode, None)
+    }
+
+    /// Length in bytes.
+    pub fn length(&self) -> usize {
+        match self {
+            Instruction::LoadLongI(_) => 4,
+            _ => 2,
+        }
+    }
+
+    /// Whether this instruction skips the next one when its condition holds.
+    pub fn is_skip(&self) -> bool {
+        use Instruction::*;
+
+        matches!(
+            self,
+            SkipEqualByte(..)
+                | SkipNotEqualByte(..)
+                | SkipEqual(..)
+                | SkipNotEqual(..)
+                | SkipPressed(..)
+                | SkipNotPressed(..)
+        )
+    }
+
+    /// Write the mnemonic, formatting code addresses with `address`.
+    pub fn fmt_with(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        address: &dyn Fn(u16) -> String,
+    ) -> fmt::Result {
+        use Instruction::*;
+
+        match *self {
+            Sys(nnn) => write!(f, "SYS {}", address(nnn)),
+            ScrollDown(n) => write!(f, "SCD {}", n),
+            Clear => write!(f, "CLS"),
+            Return => write!(f, "RET"),
+            ScrollRight => write!(f, "SCR"),
+            ScrollLeft => write!(f, "SCL"),
+            Exit => write!(f, "EXIT"),
+            Low => write!(f, "LOW"),
+            High => write!(f, "HIGH"),
+            Jump(nnn) => write!(f, "JP {}", address(nnn)),
+            Call(nnn) => write!(f, "CALL {}", address(nnn)),
+            SkipEqualByte(x, kk) => write!(f, "SE V{:X}, {:#04X}", x, kk),
+            SkipNotEqualByte(x, kk) => write!(f, "SNE V{:X}, {:#04X}", x, kk),
+            SkipEqual(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            StoreRange(x, y) => write!(f, "LD [I], V{:X} - V{:X}", x, y),
+            LoadRange(x, y) => write!(f, "LD V{:X} - V{:X}, [I]", x, y),
+            LoadByte(x, kk) => write!(f, "LD V{:X}, {:#04X}", x, kk),
+            AddByte(x, kk) => write!(f, "ADD V{:X}, {:#04X}", x, kk),
+            Load(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            Or(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            And(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            Xor(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Add(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Sub(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            ShiftRight(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            SubNegated(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            ShiftLeft(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            SkipNotEqual(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            LoadI(nnn) => write!(f, "LD I, {}", address(nnn)),
+            JumpOffset(nnn) => write!(f, "JP V0, {}", address(nnn)),
+            Random(x, kk) => write!(f, "RND V{:X}, {:#04X}", x, kk),
+            Draw(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            SkipPressed(x) => write!(f, "SKP V{:X}", x),
+            SkipNotPressed(x) => write!(f, "SKNP V{:X}", x),
+            LoadLongI(nnnn) => write!(f, "LD I, LONG {}", address(nnnn)),
+            Plane(n) => write!(f, "PLANE {}", n),
+            Audio => write!(f, "AUDIO"),
+            LoadDelay(x) => write!(f, "LD V{:X}, DT", x),
+            WaitKey(x) => write!(f, "LD V{:X}, K", x),
+            SetDelay(x) => write!(f, "LD DT, V{:X}", x),
+            SetSound(x) => write!(f, "LD ST, V{:X}", x),
+            AddI(x) => write!(f, "ADD I, V{:X}", x),
+            Font(x) => write!(f, "LD F, V{:X}", x),
+            BigFont(x) => write!(f, "LD HF, V{:X}", x),
+            Bcd(x) => write!(f, "LD B, V{:X}", x),
+            Pitch(x) => write!(f, "PITCH V{:X}", x),
+            Store(x) => write!(f, "LD [I], V{:X}", x),
+            Restore(x) => write!(f, "LD V{:X}, [I]", x),
+            StoreFlags(x) => write!(f, "LD R, V{:X}", x),
+            RestoreFlags(x) => write!(f, "LD V{:X}, R", x),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with(f, &|address| format!("{:#05X}", address))
+    }
+}
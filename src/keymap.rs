@@ -0,0 +1,168 @@
+//! Keyboard layouts for the keypad: which keyboard keys press each CHIP-8 key.
+//!
+//! Keys are named as SDL names them, after what they type rather than where they are, so the
+//! presets put the keypad on the same block of keys whatever the keyboard layout, as long as
+//! the matching preset is picked.
+
+use std::fmt;
+
+/// The CHIP-8 keys row by row, as laid out on the COSMAC VIP keypad.
+///
+/// ```text
+/// 1 2 3 C
+/// 4 5 6 D
+/// 7 8 9 E
+/// A 0 B F
+/// ```
+pub const KEYPAD_ORDER: [usize; 16] = [
+    0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF,
+];
+
+/// Names of the keyboard keys bound to each CHIP-8 key. A CHIP-8 key can have any number of
+/// them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Keymap {
+    keys: [Vec<String>; 16],
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::from_names(Keymap::PRESETS[0].1)
+    }
+}
+
+impl Keymap {
+    /// Names accepted by [`Keymap::preset`], with the key bound to every CHIP-8 key from 0 to F.
+    ///
+    /// `qwerty`, `azerty` and `dvorak` put the keypad on the 4x4 block of keys below `1` to `4`
+    /// on those layouts, `numpad` binds the digits to the keypad digits and A to F to the keys
+    /// around them, and `cosmac` binds every key to the keyboard key with its hex digit.
+    pub const PRESETS: [(&'static str, [&'static str; 16]); 5] = [
+        (
+            "qwerty",
+            [
+                "X", "1", "2", "3", "Q", "W", "E", "A", "S", "D", "Z", "C", "4", "R", "F", "V",
+            ],
+        ),
+        (
+            "azerty",
+            [
+                "X", "1", "2", "3", "A", "Z", "E", "Q", "S", "D", "W", "C", "4", "R", "F", "V",
+            ],
+        ),
+        (
+            "dvorak",
+            [
+                "Q", "1", "2", "3", "'", ",", ".", "A", "O", "E", ";", "J", "4", "P", "U", "K",
+            ],
+        ),
+        (
+            "numpad",
+            [
+                "Keypad 0",
+                "Keypad 1",
+                "Keypad 2",
+                "Keypad 3",
+                "Keypad 4",
+                "Keypad 5",
+                "Keypad 6",
+                "Keypad 7",
+                "Keypad 8",
+                "Keypad 9",
+                "Keypad /",
+                "Keypad *",
+                "Keypad -",
+                "Keypad +",
+                "Keypad Enter",
+                "Keypad .",
+            ],
+        ),
+        (
+            "cosmac",
+            [
+                "0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "A", "B", "C", "D", "E", "F",
+            ],
+        ),
+    ];
+
+    fn from_names(names: [&str; 16]) -> Self {
+        Keymap {
+            keys: names.map(|name| vec![name.to_string()]),
+        }
+    }
+
+    /// Look up a preset by name.
+    pub fn preset(name: &str) -> Option<Keymap> {
+        Keymap::PRESETS
+            .iter()
+            .find(|(preset, _)| preset.eq_ignore_ascii_case(name))
+            .map(|&(_, names)| Keymap::from_names(names))
+    }
+
+    /// Names of the keyboard keys bound to CHIP-8 `key`.
+    pub fn keys(&self, key: usize) -> &[String] {
+        &self.keys[key]
+    }
+
+    /// Bind CHIP-8 `key` to the keyboard keys called `names`, instead of the ones it had.
+    pub fn bind(&mut self, key: usize, names: Vec<String>) {
+        self.keys[key] = names;
+    }
+}
+
+/// Formats the keymap as a TOML inline table, as the `keymap` setting of the
+/// [configuration](crate::config) takes it.
+impl fmt::Display for Keymap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{ ")?;
+
+        for (key, names) in self.keys.iter().enumerate() {
+            if key > 0 {
+                write!(f, ", ")?;
+            }
+
+            write!(f, "{:X} = {:?}", key, names)?;
+        }
+
+        write!(f, " }}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn presets_by_name() {
+        let azerty = Keymap::preset("AZERTY").unwrap();
+        assert_eq!(azerty.keys(0x5), ["Z"]);
+        assert_eq!(
+            Keymap::preset("numpad").unwrap().keys(0xE),
+            ["Keypad Enter"]
+        );
+        assert_eq!(Keymap::preset("colemak"), None);
+        assert_eq!(Keymap::default(), Keymap::preset("qwerty").unwrap());
+    }
+
+    #[test]
+    fn bind_replaces_the_keys() {
+        let mut keymap = Keymap::default();
+        keymap.bind(0x5, vec!["Up".into(), "W".into()]);
+        keymap.bind(0x8, Vec::new());
+
+        assert_eq!(keymap.keys(0x5), ["Up", "W"]);
+        assert!(keymap.keys(0x8).is_empty());
+        assert_eq!(keymap.keys(0x4), ["Q"]);
+    }
+
+    #[test]
+    fn display_is_a_keymap_setting() {
+        let mut keymap = Keymap::preset("dvorak").unwrap();
+        keymap.bind(0x5, vec!["Up".into(), "Keypad 8".into()]);
+        keymap.bind(0xF, Vec::new());
+
+        let config = Config::from_toml(&format!("[defaults]\nkeymap = {}\n", keymap)).unwrap();
+        assert_eq!(config.defaults.keymap.apply(Keymap::default()), keymap);
+    }
+}
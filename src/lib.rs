@@ -0,0 +1,55 @@
+//! A CHIP-8 interpreter.
+//!
+//! The core is [`Machine`], which has no dependency on any windowing or audio library and can
+//! be stepped from anywhere:
+//!
+//! ```no_run
+//! use chip8::{Machine, Rom};
+//!
+//! let rom = Rom::open("pong.ch8").unwrap();
+//! let mut machine = Machine::new();
+//! machine.load(&rom).unwrap();
+//!
+//! loop {
+//!     machine.keypad.set(0x1, true);
+//!     machine.run_frame().unwrap();
+//!     let _pixels = machine.display.rows();
+//! }
+//! ```
+//!
+//! The SDL frontend used by the `chip8` binary lives in [`frontend`], behind the default `sdl`
+//! feature.
+
+pub mod asm;
+pub mod audio;
+pub mod config;
+pub mod database;
+pub mod debugger;
+pub mod disasm;
+pub mod error;
+#[cfg(feature = "sdl")]
+pub mod frontend;
+pub mod input;
+pub mod instruction;
+pub mod keymap;
+pub mod machine;
+pub mod movie;
+pub mod palette;
+pub mod platform;
+pub mod quirks;
+pub mod random;
+pub mod render;
+pub mod rewind;
+pub mod rom;
+pub mod screenshot;
+pub mod state;
+pub mod video;
+
+pub use error::EmulatorError;
+pub use input::Keypad;
+pub use instruction::Instruction;
+pub use machine::Machine;
+pub use platform::Platform;
+pub use quirks::Quirks;
+pub use rom::Rom;
+pub use video::Display;
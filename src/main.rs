@@ -1,26 +1,703 @@
-use std::{env, process};
+use std::{
+    path::{Path, PathBuf},
+    process,
+    time::Instant,
+};
 
-use cpu::CPU;
+use chip8::{
+    audio::{Tone, Waveform},
+    config::{Config, KeymapOverrides, Profile, QuirkOverrides},
+    database::{Database, Entry},
+    disasm,
+    frontend::{Frontend, Settings},
+    keymap::Keymap,
+    machine::{self, FRAME_RATE},
+    movie::{Movie, Player, Recorder},
+    palette::{Color, Palette, PixelStyle},
+    random,
+    render::Renderer,
+    rewind,
+    screenshot::Screenshots,
+    video::Persistence,
+    EmulatorError, Machine, Platform, Quirks, Rom,
+};
 
-mod audio;
-mod cpu;
-mod input;
-mod video;
+const HELP: &str = "\
+Usage: chip8 [COMMAND] ROM [OPTIONS]
+
+Commands:
+  run        Play ROM in a window (the default)
+  headless   Run ROM without a window and print the final machine state
+  bench      Run ROM without a window as fast as possible and print the speed
+  disasm     Print an annotated disassembly of ROM
+  info       Print the size, hash, platform and code size of ROM
+
+Machine options:
+  -p, --platform NAME   chip8, schip or xochip [default: from the ROM extension]
+  -q, --quirks NAME     Quirk preset: vip, chip48, schip or xochip [default: from the platform]
+  -c, --clock HZ        Instructions per second [default: 500]
+  -i, --ipf N           Instructions per 60 Hz frame, instead of --clock
+      --seed N          Seed of the random number generator [default: random]
+      --random NAME     Random number generator: xorshift or counter [default: xorshift]
+      --play MOVIE      Replay the keys recorded in MOVIE, with the machine it was recorded on
+      --record MOVIE    Record the keys pressed to MOVIE (run only)
+
+Configuration options:
+      --config FILE     Read settings from FILE [default: chip8/config.toml in the user
+                        configuration directory, if there is one]
+      --no-config       Ignore the configuration file
+      --database DIR    Look ROMs up in the chip-8-database files in DIR before the built-in
+                        ones [default: chip8/database in the user configuration directory, if
+                        there is one]
+
+Window options:
+  -s, --scale N         Size in screen pixels of a lo-res pixel [default: 20]
+      --fg COLOR        Colour of lit pixels, as RRGGBB [default: FFFFFF]
+      --bg COLOR        Colour of unlit pixels, as RRGGBB [default: 000000]
+      --palette NAME    Palette: mono, green, amber, lcd, high-contrast or colorblind, or up
+                        to 4 colours as RRGGBB separated by commas: background, foreground,
+                        then the XO-CHIP colours [default: mono]
+      --pixels STYLE    Shape of pixels: square, grid or led [default: square]
+      --persistence P   Keep pixels that go off showing, against flicker: a number of frames
+                        to fade them out over, fade (4 frames), blend (with the frame before)
+                        or off [default: off]
+  -f, --fullscreen      Start in fullscreen
+      --vsync           Present frames in step with the refresh of the monitor
+  -k, --keymap NAME     Keyboard layout: qwerty, azerty, dvorak, numpad or cosmac
+                        [default: qwerty]
+      --key-beep        Sound the buzzer while a key is held for Fx0A, as the COSMAC VIP did
+      --tone-freq HZ    Frequency of the buzzer [default: 440]
+      --volume V        Volume of the buzzer, from 0 to 1 [default: 0.25]
+      --waveform NAME   Shape of the buzzer tone: square, triangle, sawtooth or sine
+                        [default: square]
+      --rewind-memory MIB
+                        Memory kept for rewinding with Backspace [default: 16]
+      --rewind-interval N
+                        Frames between two rewind snapshots [default: 2]
+
+      --screenshot-dir DIR
+                        Directory screenshots are saved in, by F12 or --screenshot
+                        [default: the current directory]
+
+Headless options:
+  -n, --frames N        Frames to run [default: the whole movie played, or 600]
+      --screenshot      Save a screenshot of the last frame, as it is and at --scale
+
+  -h, --help            Print this help
+  -V, --version         Print the version
+
+Options given here override the configuration file, whose [defaults] apply to every ROM
+and whose [rom.<SHA-1>] sections apply to the ROM with that hash, as printed by info. What
+the ROM database knows about the ROM applies between the two.
+
+Exit status: 0 on success, 1 if the ROM, the configuration or the database can't be loaded
+or the machine fails, 2 on an invalid command line, 3 if a replayed movie desyncs.
+";
+
+/// Exit status when the ROM, the configuration or the database can't be loaded, or the
+/// machine fails.
+const EXIT_FAILURE: i32 = 1;
+/// Exit status of an invalid command line.
+const EXIT_USAGE: i32 = 2;
+/// Exit status when a replayed movie desyncs.
+const EXIT_DESYNC: i32 = 3;
+
+/// Frames run by `headless` and `bench` without `--frames` or a movie.
+const DEFAULT_FRAMES: usize = 600;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Command {
+    Run,
+    Headless,
+    Bench,
+    Disasm,
+    Info,
+}
+
+impl Command {
+    const NAMES: [(&'static str, Command); 5] = [
+        ("run", Command::Run),
+        ("headless", Command::Headless),
+        ("bench", Command::Bench),
+        ("disasm", Command::Disasm),
+        ("info", Command::Info),
+    ];
+
+    fn from_name(name: &str) -> Option<Command> {
+        Command::NAMES
+            .iter()
+            .find(|(command, _)| *command == name)
+            .map(|&(_, command)| command)
+    }
+}
+
+/// Everything the command line asks for.
+struct Options {
+    command: Command,
+    rom: PathBuf,
+    /// Machine settings from the command line, and once the ROM is open, from the
+    /// configuration with the command line applied on top.
+    profile: Profile,
+    config: Option<PathBuf>,
+    no_config: bool,
+    database: Option<PathBuf>,
+    seed: Option<u64>,
+    random: &'static str,
+    play: Option<PathBuf>,
+    record: Option<PathBuf>,
+    frames: Option<usize>,
+    screenshot: bool,
+    settings: Settings,
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut options = match parse_args() {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("chip8: {}\nTry 'chip8 --help' for more information.", err);
+            process::exit(EXIT_USAGE)
+        }
+    };
+
+    let rom = Rom::open(&options.rom).unwrap_or_else(|err| fail(&options.rom, err));
+
+    let local = local_database(&options);
+    let entry = Database::lookup_first(local.iter().chain([Database::embedded()]), &rom);
+
+    let known = entry.map(|entry| entry.profile());
+    options.profile = configured_profile(&options, &rom, known.as_ref());
+
+    match options.command {
+        Command::Run => run(&options, &rom, entry),
+        Command::Headless => headless(&options, &rom),
+        Command::Bench => bench(&options, &rom),
+        Command::Disasm => print!("{}", disasm::disassemble(&rom, platform(&options))),
+        Command::Info => info(&options, &rom, entry),
+    }
+}
+
+fn parse_args() -> Result<Options, lexopt::Error> {
+    use lexopt::prelude::*;
+
+    let mut parser = lexopt::Parser::from_env();
+
+    let mut command = None;
+    let mut rom = None;
+    let mut options = Options {
+        command: Command::Run,
+        rom: PathBuf::new(),
+        profile: Profile::default(),
+        config: None,
+        no_config: false,
+        database: None,
+        seed: None,
+        random: random::NAMES[0],
+        play: None,
+        record: None,
+        frames: None,
+        screenshot: false,
+        settings: Settings::default(),
+    };
+
+    while let Some(arg) = parser.next()? {
+        match arg {
+            Short('h') | Long("help") => {
+                print!("{}", HELP);
+                process::exit(0)
+            }
+            Short('V') | Long("version") => {
+                println!("chip8 {}", env!("CARGO_PKG_VERSION"));
+                process::exit(0)
+            }
+            Short('p') | Long("platform") => {
+                options.profile.platform = Some(parser.value()?.parse_with(|name| {
+                    Platform::from_name(name)
+                        .ok_or_else(|| one_of(Platform::NAMES.iter().map(|(name, _)| *name)))
+                })?)
+            }
+            Short('q') | Long("quirks") => {
+                options.profile.quirks = QuirkOverrides {
+                    preset: Some(parser.value()?.parse_with(|name| {
+                        Quirks::preset(name)
+                            .ok_or_else(|| one_of(Quirks::PRESETS.iter().map(|(name, _)| *name)))
+                    })?),
+                    ..QuirkOverrides::default()
+                }
+            }
+            Short('k') | Long("keymap") => {
+                options.profile.keymap = KeymapOverrides {
+                    preset: Some(parser.value()?.parse_with(|name| {
+                        Keymap::preset(name)
+                            .ok_or_else(|| one_of(Keymap::PRESETS.iter().map(|(name, _)| *name)))
+                    })?),
+                    ..KeymapOverrides::default()
+                }
+            }
+            Short('c') | Long("clock") => {
+                options.profile.clock =
+                    Some(parser.value()?.parse_with(|value| match value.parse() {
+                        Ok(clock) if clock > 0.0 => Ok(clock),
+                        _ => Err("expected a positive number of instructions per second"),
+                    })?)
+            }
+            Short('i') | Long("ipf") => {
+                let ipf = parser.value()?.parse_with(positive)?;
+                options.profile.clock = Some(ipf as f32 * FRAME_RATE)
+            }
+            Long("seed") => options.seed = Some(parser.value()?.parse()?),
+            Long("random") => {
+                options.random = parser.value()?.parse_with(|name| {
+                    random::NAMES
+                        .into_iter()
+                        .find(|random| random.eq_ignore_ascii_case(name))
+                        .ok_or_else(|| one_of(random::NAMES))
+                })?
+            }
+            Long("play") => options.play = Some(parser.value()?.into()),
+            Long("record") => options.record = Some(parser.value()?.into()),
+            Short('s') | Long("scale") => {
+                options.settings.scale = parser.value()?.parse_with(positive)? as u32
+            }
+            Long("fg") => options.profile.palette[1] = Some(parser.value()?.parse_with(color)?),
+            Long("bg") => options.profile.palette[0] = Some(parser.value()?.parse_with(color)?),
+            Long("palette") => options.profile.palette = parser.value()?.parse_with(palette)?,
+            Long("persistence") => {
+                options.profile.persistence = Some(parser.value()?.parse_with(|name| {
+                    Persistence::from_name(name)
+                        .ok_or("expected a number of frames, fade, blend or off")
+                })?)
+            }
+            Long("pixels") => {
+                options.profile.pixels = Some(parser.value()?.parse_with(|name| {
+                    PixelStyle::from_name(name)
+                        .ok_or_else(|| one_of(PixelStyle::NAMES.iter().map(|(name, _)| *name)))
+                })?)
+            }
+            Short('f') | Long("fullscreen") => options.settings.fullscreen = true,
+            Long("vsync") => options.settings.vsync = true,
+            Long("key-beep") => options.settings.key_beep = true,
+            Long("tone-freq") => {
+                options.profile.tone_freq =
+                    Some(parser.value()?.parse_with(|value| match value.parse() {
+                        Ok(frequency) if frequency > 0.0 => Ok(frequency),
+                        _ => Err("expected a positive frequency in Hz"),
+                    })?)
+            }
+            Long("volume") => {
+                options.profile.volume =
+                    Some(parser.value()?.parse_with(|value| match value.parse() {
+                        Ok(volume) if (0.0..=1.0).contains(&volume) => Ok(volume),
+                        _ => Err("expected a volume from 0 to 1"),
+                    })?)
+            }
+            Long("rewind-memory") => {
+                options.profile.rewind_memory = Some(parser.value()?.parse_with(positive)? as u32)
+            }
+            Long("rewind-interval") => {
+                options.profile.rewind_interval = Some(parser.value()?.parse_with(positive)? as u32)
+            }
+            Long("waveform") => {
+                options.profile.waveform = Some(parser.value()?.parse_with(|name| {
+                    Waveform::from_name(name)
+                        .ok_or_else(|| one_of(Waveform::NAMES.iter().map(|(name, _)| *name)))
+                })?)
+            }
+            Short('n') | Long("frames") => options.frames = Some(parser.value()?.parse()?),
+            Long("config") => options.config = Some(parser.value()?.into()),
+            Long("no-config") => options.no_config = true,
+            Long("database") => options.database = Some(parser.value()?.into()),
+            Long("screenshot") => options.screenshot = true,
+            Long("screenshot-dir") => options.profile.screenshots = Some(parser.value()?.into()),
+            Value(value) if command.is_none() && rom.is_none() => {
+                let value = value.string()?;
+
+                match Command::from_name(&value) {
+                    Some(found) => command = Some(found),
+                    None => rom = Some(PathBuf::from(value)),
+                }
+            }
+            Value(value) if rom.is_none() => rom = Some(PathBuf::from(value)),
+            _ => return Err(arg.unexpected()),
+        }
+    }
+
+    options.command = command.unwrap_or(Command::Run);
+    options.rom = rom.ok_or("missing ROM")?;
+
+    if options.record.is_some() && options.command != Command::Run {
+        return Err("--record only works with run".into());
+    }
+
+    if options.screenshot && options.command != Command::Headless {
+        return Err("--screenshot only works with headless".into());
+    }
+
+    if options.record.is_some() && options.play.is_some() {
+        return Err("--record and --play can't be used together".into());
+    }
+
+    if options.config.is_some() && options.no_config {
+        return Err("--config and --no-config can't be used together".into());
+    }
+
+    Ok(options)
+}
+
+fn one_of<'a, I: IntoIterator<Item = &'a str>>(names: I) -> String {
+    format!(
+        "expected one of {}",
+        names.into_iter().collect::<Vec<_>>().join(", ")
+    )
+}
+
+fn positive(value: &str) -> Result<usize, &'static str> {
+    match value.parse() {
+        Ok(value) if value > 0 => Ok(value),
+        _ => Err("expected a positive whole number"),
+    }
+}
+
+fn color(value: &str) -> Result<Color, &'static str> {
+    Color::from_hex(value).ok_or("expected a colour as RRGGBB")
+}
+
+fn palette(value: &str) -> Result<[Option<Color>; 4], String> {
+    Palette::partial(value).ok_or_else(|| {
+        format!(
+            "{}, or up to 4 colours as RRGGBB separated by commas",
+            one_of(Palette::PRESETS.iter().map(|(name, _)| *name))
+        )
+    })
+}
+
+/// Report `err` about `path` and exit.
+fn fail<P: AsRef<Path>>(path: P, err: EmulatorError) -> ! {
+    eprintln!("{}: {}", path.as_ref().display(), err);
+    process::exit(EXIT_FAILURE)
+}
+
+/// The ROM database given on the command line or found in the default directory, if any.
+fn local_database(options: &Options) -> Option<Database> {
+    if let Some(dir) = &options.database {
+        Some(Database::load_dir(dir).unwrap_or_else(|err| fail(dir, err)))
+    } else if let Some(dir) = Database::default_dir() {
+        Database::load_dir_if_exists(&dir).unwrap_or_else(|err| fail(&dir, err))
+    } else {
+        None
+    }
+}
+
+/// The settings configured for `rom` on top of `known`, with the ones given on the command
+/// line applied last.
+fn configured_profile(options: &Options, rom: &Rom, known: Option<&Profile>) -> Profile {
+    let config = if options.no_config {
+        Config::default()
+    } else if let Some(path) = &options.config {
+        Config::load_file(path).unwrap_or_else(|err| fail(path, err))
+    } else if let Some(path) = Config::default_path() {
+        Config::load_file_or_default(&path).unwrap_or_else(|err| fail(&path, err))
+    } else {
+        Config::default()
+    };
+
+    let mut profile = config.profile(rom, known);
+    profile.merge(&options.profile);
+    profile
+}
+
+/// The platform asked for, or else the one implied by the ROM extension.
+fn platform(options: &Options) -> Platform {
+    options.profile.platform.unwrap_or_else(|| {
+        options
+            .rom
+            .extension()
+            .and_then(|extension| Platform::from_extension(&extension.to_string_lossy()))
+            .unwrap_or_default()
+    })
+}
+
+/// The default palette with the configured colours.
+fn configured_palette(options: &Options) -> Palette {
+    let mut palette = Palette::default();
+
+    for (color, configured) in palette.colors.iter_mut().zip(options.profile.palette) {
+        *color = configured.unwrap_or(*color);
+    }
+
+    palette
+}
+
+/// The default buzzer tone with the configured frequency, volume and waveform.
+fn configured_tone(options: &Options) -> Tone {
+    let tone = Tone::default();
 
-    if args.len() < 2 {
-        println!("Missing rom file");
-        process::exit(1)
+    Tone {
+        frequency: options.profile.tone_freq.unwrap_or(tone.frequency),
+        volume: options.profile.volume.unwrap_or(tone.volume),
+        waveform: options.profile.waveform.unwrap_or(tone.waveform),
     }
+}
+
+fn screenshot_dir(options: &Options) -> PathBuf {
+    options
+        .profile
+        .screenshots
+        .clone()
+        .unwrap_or_else(|| options.settings.screenshot_dir.clone())
+}
+
+/// Set up a machine with `rom` loaded as the options say, or as a played back movie says. Unless
+/// a movie is played, its random number generator is seeded with `seed`.
+fn machine(options: &Options, rom: &Rom, seed: u64) -> (Machine, Option<Player>) {
+    let persistence = options.profile.persistence.unwrap_or_default();
+
+    if let Some(path) = &options.play {
+        let movie = Movie::load_file(path).unwrap_or_else(|err| fail(path, err));
+        let mut machine = movie.machine(rom).unwrap_or_else(|err| fail(path, err));
+        machine.display.persistence = persistence;
+        return (machine, Some(Player::new(movie)));
+    }
+
+    let mut machine = Machine::with_platform(platform(options));
 
-    let rom_path = &args[1];
+    machine.quirks = options.profile.quirks.apply(machine.quirks);
+    machine.display.persistence = persistence;
+
+    if let Some(clock) = options.profile.clock {
+        machine.instructions_per_frame = machine::instructions_per_frame(clock);
+    }
+
+    machine.set_random(random::from_name(options.random, seed).unwrap());
+
+    machine
+        .load(rom)
+        .unwrap_or_else(|err| fail(&options.rom, err));
+
+    (machine, None)
+}
+
+/// Print the registers of `machine`, after it stopped with `err`.
+fn report_crash(machine: &Machine, err: &EmulatorError) {
+    eprintln!("Error: {}", err);
+    eprintln!("PC: {:#05X}  I: {:#05X}", machine.pc(), machine.i());
+    eprintln!("V:  {:02X?}", machine.v());
+    eprintln!("Stack: {:03X?}", machine.stack());
+}
+
+fn run(options: &Options, rom: &Rom, entry: Option<Entry>) {
+    match &options.profile.name {
+        Some(name) => println!("{} ({})", name, options.rom.display()),
+        None => println!("{}", options.rom.display()),
+    }
+
+    if let Some(entry) = entry.filter(|entry| !entry.rom.keys.is_empty()) {
+        let keys: Vec<String> = entry
+            .rom
+            .keys
+            .iter()
+            .map(|(action, key)| format!("{} {:X}", action, key))
+            .collect();
+        println!("Keys: {}", keys.join(", "));
+    }
+
+    let seed = options.seed.unwrap_or_else(rand::random);
+    let (mut machine, player) = machine(options, rom, seed);
 
     let sdl = sdl2::init().unwrap();
+    let settings = Settings {
+        title: options
+            .profile
+            .name
+            .clone()
+            .unwrap_or_else(|| "chip8".into()),
+        state_path: options.rom.clone(),
+        keymap: options.profile.keymap.apply(Keymap::default()),
+        palette: configured_palette(options),
+        pixels: options.profile.pixels.unwrap_or_default(),
+        screenshot_dir: screenshot_dir(options),
+        tone: configured_tone(options),
+        rewind_memory: options
+            .profile
+            .rewind_memory
+            .map_or(rewind::DEFAULT_MEMORY, |mib| mib as usize * 1024 * 1024),
+        rewind_interval: options
+            .profile
+            .rewind_interval
+            .unwrap_or(rewind::DEFAULT_INTERVAL),
+        ..options.settings.clone()
+    };
+
+    let mut frontend = Frontend::new(&sdl, &settings);
+
+    if let Some(player) = player {
+        frontend.play(player);
+    } else if options.record.is_some() {
+        frontend.record(Recorder::new(&mut machine, rom, seed));
+    }
 
-    let mut cpu = CPU::new(&sdl, &rom_path);
-    while cpu.tick() {}
+    loop {
+        match frontend.tick(&mut machine) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(err) => {
+                // Keep the window open, paused on the faulting instruction, so that the
+                // machine can be looked into.
+                report_crash(&machine, &err);
+                frontend.show_error(&err);
+                frontend.break_into_debugger(&machine);
+            }
+        }
+    }
 
+    save_recording(&mut frontend, options.record.as_deref());
     println!("Closed.")
 }
+
+/// Write the movie being recorded, if any, to `path`.
+fn save_recording(frontend: &mut Frontend, path: Option<&Path>) {
+    let (Some(movie), Some(path)) = (frontend.take_recording(), path) else {
+        return;
+    };
+
+    match movie.save_file(path) {
+        Ok(()) => println!("Recorded {} frames to {}", movie.len(), path.display()),
+        Err(err) => eprintln!("{}: {}", path.display(), err),
+    }
+}
+
+/// Run `machine` without a window for the frames asked for, or until it exits. Returns the
+/// number of frames run, and exits the process if the machine fails.
+fn run_headless(options: &Options, machine: &mut Machine, player: &mut Option<Player>) -> usize {
+    let frames = match (options.frames, &player) {
+        (Some(frames), _) => frames,
+        (None, Some(_)) => usize::MAX,
+        (None, None) => DEFAULT_FRAMES,
+    };
+
+    let mut run = 0;
+
+    while run < frames && !machine.exited() {
+        if let Some(player) = player {
+            if player.finished() && options.frames.is_none() {
+                break;
+            }
+
+            player.before_frame(&mut machine.keypad);
+        }
+
+        if let Err(err) = machine.run_frame() {
+            report_crash(machine, &err);
+            process::exit(EXIT_FAILURE)
+        }
+
+        run += 1;
+
+        if let Some(player) = player {
+            if !player.after_frame(machine) {
+                eprintln!("Movie desynced at frame {}", player.frame());
+            }
+        }
+    }
+
+    run
+}
+
+/// Exit with [`EXIT_DESYNC`] if `player` went out of sync.
+fn check_desync(player: &Option<Player>) {
+    if player
+        .as_ref()
+        .is_some_and(|player| player.desync().is_some())
+    {
+        process::exit(EXIT_DESYNC)
+    }
+}
+
+fn headless(options: &Options, rom: &Rom) {
+    let seed = options.seed.unwrap_or_else(rand::random);
+    let (mut machine, mut player) = machine(options, rom, seed);
+    let frames = run_headless(options, &mut machine, &mut player);
+
+    println!("Frames: {}", frames);
+    println!("PC: {:#05X}  I: {:#05X}", machine.pc(), machine.i());
+    println!("V:  {:02X?}", machine.v());
+    println!("Stack: {:03X?}", machine.stack());
+
+    if options.screenshot {
+        let dir = screenshot_dir(options);
+        let screenshots = Screenshots::new(dir.clone(), &options.rom);
+        let pixels = options.profile.pixels.unwrap_or_default();
+        let mut renderer = Renderer::new(configured_palette(options), pixels);
+        let scale = options.settings.scale as usize;
+
+        match screenshots.take(&mut renderer, &machine.display, scale) {
+            Ok(paths) => {
+                for path in paths {
+                    println!("Screenshot: {}", path.display());
+                }
+            }
+            Err(err) => fail(dir, err),
+        }
+    }
+
+    check_desync(&player)
+}
+
+fn bench(options: &Options, rom: &Rom) {
+    let seed = options.seed.unwrap_or_else(rand::random);
+    let (mut machine, mut player) = machine(options, rom, seed);
+
+    let start = Instant::now();
+    let frames = run_headless(options, &mut machine, &mut player);
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let rate = frames as f64 / elapsed;
+    println!(
+        "{} frames in {:.3} s: {:.0} frames per second, {:.1} times real time",
+        frames,
+        elapsed,
+        rate,
+        rate / FRAME_RATE as f64
+    );
+
+    check_desync(&player)
+}
+
+fn info(options: &Options, rom: &Rom, entry: Option<Entry>) {
+    let platform = platform(options);
+    let disassembly = disasm::disassemble(rom, platform);
+    let code = disassembly.code();
+    let code_size: usize = code.values().map(|instruction| instruction.length()).sum();
+
+    println!("File:      {}", options.rom.display());
+
+    if let Some(name) = &options.profile.name {
+        println!("Name:      {}", name);
+    }
+
+    if let Some(program) = entry.map(|entry| entry.program) {
+        if !program.authors.is_empty() {
+            println!("Authors:   {}", program.authors.join(", "));
+        }
+
+        if let Some(release) = &program.release {
+            println!("Released:  {}", release);
+        }
+
+        if let Some(description) = &program.description {
+            println!("About:     {}", description);
+        }
+    }
+
+    println!("Size:      {} bytes", rom.bytes().len());
+    println!("SHA-1:     {}", rom.sha1_hex());
+    println!("Platform:  {}", platform.name());
+    println!(
+        "Code:      {} instructions, {} bytes",
+        code.len(),
+        code_size
+    );
+    println!(
+        "Data:      {} bytes",
+        rom.bytes().len().saturating_sub(code_size)
+    );
+}
@@ -0,0 +1,446 @@
+//! Input movies: the keys held in every frame of a run, replayed to reproduce it exactly.
+//!
+//! A run only depends on the ROM, the machine settings, the random seed and the keys, so that
+//! is all a [`Movie`] stores. Every [`CHECKPOINT_INTERVAL`] frames it also stores a hash of the
+//! machine state, which playback compares against to notice when it went out of sync, for
+//! example because the emulator changed since the movie was recorded.
+//!
+//! The file is [`MAGIC`] and the format version, like a [save state](crate::state), then the
+//! ROM SHA-1, the name of the random number generator and its seed, the platform, the
+//! instructions per frame and the quirks, then the keypad of every frame as a `u16` bitmask
+//! and the checkpoints as frame number and hash.
+//!
+//! Version 1 movies were recorded with a generator this build no longer has, so they can't
+//! be replayed and are refused with [`EmulatorError::UnsupportedMovie`]. Version 3 lets the
+//! memory increment quirk be 2, as in version 5 [save states](crate::state); version 2
+//! movies read the same.
+
+use std::{fs, path::Path};
+
+use crate::{
+    error::EmulatorError,
+    input::Keypad,
+    machine::Machine,
+    platform::Platform,
+    quirks::Quirks,
+    random,
+    rom::Rom,
+    state::{Reader, Writer},
+};
+
+/// Bytes every movie starts with.
+pub const MAGIC: [u8; 4] = *b"C8MV";
+/// Format version written by this build.
+pub const VERSION: u16 = 3;
+/// Frames between two state hashes.
+pub const CHECKPOINT_INTERVAL: usize = 60;
+
+/// A recorded run.
+pub struct Movie {
+    /// SHA-1 of the ROM the movie was recorded with.
+    pub rom_hash: [u8; 20],
+    /// Name of the random number generator, one of [`random::NAMES`].
+    pub random: String,
+    /// Seed of the random number generator.
+    pub seed: u64,
+    pub platform: Platform,
+    pub quirks: Quirks,
+    pub instructions_per_frame: usize,
+    /// Keys held in every frame, one bit per key.
+    frames: Vec<u16>,
+    /// Frame numbers and hashes of the state after them.
+    checkpoints: Vec<(usize, u64)>,
+}
+
+impl Movie {
+    /// Number of frames recorded.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// A machine set up like the one the movie was recorded on, with `rom` loaded.
+    ///
+    /// A movie recorded with a generator that isn't built in is reported as damaged.
+    pub fn machine(&self, rom: &Rom) -> Result<Machine, EmulatorError> {
+        if rom.sha1() != self.rom_hash {
+            return Err(EmulatorError::MovieRomMismatch);
+        }
+
+        let random =
+            random::from_name(&self.random, self.seed).ok_or(EmulatorError::CorruptMovie)?;
+
+        let mut machine = Machine::with_platform(self.platform);
+        machine.quirks = self.quirks;
+        machine.instructions_per_frame = self.instructions_per_frame;
+        machine.set_random(random);
+        machine.load(rom)?;
+        Ok(machine)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new(MAGIC, VERSION);
+
+        writer.bytes(&self.rom_hash);
+        writer.u8(self.random.len() as u8);
+        writer.bytes(self.random.as_bytes());
+        writer.u64(self.seed);
+
+        let platform = Platform::NAMES
+            .iter()
+            .position(|&(_, platform)| platform == self.platform)
+            .unwrap();
+        writer.u8(platform as u8);
+
+        writer.u32(self.instructions_per_frame as u32);
+        self.quirks.save(&mut writer);
+
+        writer.u32(self.frames.len() as u32);
+        for &keys in &self.frames {
+            writer.u16(keys);
+        }
+
+        writer.u32(self.checkpoints.len() as u32);
+        for &(frame, hash) in &self.checkpoints {
+            writer.u32(frame as u32);
+            writer.u64(hash);
+        }
+
+        writer.finish()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, EmulatorError> {
+        Movie::read(bytes).map_err(|err| match err {
+            EmulatorError::CorruptState => EmulatorError::CorruptMovie,
+            EmulatorError::UnsupportedState { version } => {
+                EmulatorError::UnsupportedMovie { version }
+            }
+            err => err,
+        })
+    }
+
+    fn read(bytes: &[u8]) -> Result<Self, EmulatorError> {
+        let mut reader = Reader::new(bytes, MAGIC, VERSION)?;
+
+        if reader.version < 2 {
+            return Err(EmulatorError::UnsupportedState {
+                version: reader.version,
+            });
+        }
+
+        let rom_hash = reader.array()?;
+        let len = reader.u8()? as usize;
+        let random = String::from_utf8_lossy(reader.bytes(len)?).into_owned();
+        let seed = reader.u64()?;
+
+        let platform = Platform::NAMES
+            .get(reader.u8()? as usize)
+            .ok_or(EmulatorError::CorruptState)?
+            .1;
+
+        let instructions_per_frame = reader.u32()? as usize;
+        let quirks = Quirks::load(&mut reader)?;
+
+        let frames = (0..reader.u32()?)
+            .map(|_| reader.u16())
+            .collect::<Result<_, _>>()?;
+
+        let checkpoints = (0..reader.u32()?)
+            .map(|_| Ok((reader.u32()? as usize, reader.u64()?)))
+            .collect::<Result<_, EmulatorError>>()?;
+
+        reader.finish()?;
+
+        Ok(Movie {
+            rom_hash,
+            random,
+            seed,
+            platform,
+            quirks,
+            instructions_per_frame,
+            frames,
+            checkpoints,
+        })
+    }
+
+    pub fn save_file<P: AsRef<Path>>(&self, path: P) -> Result<(), EmulatorError> {
+        fs::write(path, self.to_bytes()).map_err(EmulatorError::MovieIo)
+    }
+
+    pub fn load_file<P: AsRef<Path>>(path: P) -> Result<Self, EmulatorError> {
+        Movie::from_bytes(&fs::read(path).map_err(EmulatorError::MovieIo)?)
+    }
+}
+
+/// Records a run into a [`Movie`].
+pub struct Recorder {
+    movie: Movie,
+    /// Whether a frame has started and not completed yet.
+    in_frame: bool,
+}
+
+impl Recorder {
+    /// Start recording `machine`, which must have just loaded `rom` and not run yet. Its
+    /// random number generator is seeded with `seed`.
+    pub fn new(machine: &mut Machine, rom: &Rom, seed: u64) -> Self {
+        machine.seed(seed);
+
+        Recorder {
+            movie: Movie {
+                rom_hash: rom.sha1(),
+                random: machine.random().name().to_string(),
+                seed,
+                platform: machine.platform(),
+                quirks: machine.quirks,
+                instructions_per_frame: machine.instructions_per_frame,
+                frames: Vec::new(),
+                checkpoints: Vec::new(),
+            },
+            in_frame: false,
+        }
+    }
+
+    /// Call before running the machine with the keys really held: at the start of a frame,
+    /// sets `keypad` to them and records them. A frame left unfinished, when the debugger
+    /// paused in the middle of it, goes on with the keys recorded when it started.
+    pub fn before_frame(&mut self, keypad: &mut Keypad, pressed: [bool; 16]) {
+        if self.in_frame {
+            return;
+        }
+
+        self.in_frame = true;
+        let keys = (0..16).fold(0, |bits, key| bits | (pressed[key] as u16) << key);
+        apply_keys(keypad, keys);
+        self.movie.frames.push(keys);
+    }
+
+    /// Call after every frame completed.
+    pub fn after_frame(&mut self, machine: &Machine) {
+        self.in_frame = false;
+        let frame = self.movie.frames.len();
+
+        if frame.is_multiple_of(CHECKPOINT_INTERVAL) {
+            self.movie.checkpoints.push((frame, state_hash(machine)));
+        }
+    }
+
+    pub fn finish(self) -> Movie {
+        self.movie
+    }
+}
+
+/// Plays a [`Movie`] back.
+pub struct Player {
+    movie: Movie,
+    frame: usize,
+    checkpoint: usize,
+    desync: Option<usize>,
+}
+
+impl Player {
+    /// Play `movie` on a machine made by [`Movie::machine`].
+    pub fn new(movie: Movie) -> Self {
+        Player {
+            movie,
+            frame: 0,
+            checkpoint: 0,
+            desync: None,
+        }
+    }
+
+    /// Number of frames played.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Whether every frame has been played.
+    pub fn finished(&self) -> bool {
+        self.frame >= self.movie.frames.len()
+    }
+
+    /// The first frame after which the machine didn't match the recording, if any.
+    pub fn desync(&self) -> Option<usize> {
+        self.desync
+    }
+
+    /// Call before running the machine: sets `keypad` to the keys recorded for the current
+    /// frame, including when it is resumed after a pause. Does nothing once the movie is
+    /// finished.
+    pub fn before_frame(&mut self, keypad: &mut Keypad) {
+        if let Some(&keys) = self.movie.frames.get(self.frame) {
+            apply_keys(keypad, keys);
+        }
+    }
+
+    /// Call after every frame completed. Returns `false` the first time the machine is found to differ
+    /// from the recording.
+    pub fn after_frame(&mut self, machine: &Machine) -> bool {
+        if self.finished() {
+            return true;
+        }
+
+        self.frame += 1;
+
+        let Some(&(frame, hash)) = self.movie.checkpoints.get(self.checkpoint) else {
+            return true;
+        };
+
+        if frame != self.frame {
+            return true;
+        }
+
+        self.checkpoint += 1;
+
+        if self.desync.is_none() && state_hash(machine) != hash {
+            self.desync = Some(self.frame);
+            return false;
+        }
+
+        true
+    }
+}
+
+/// Press and release keys of `keypad` to match the bitmask `keys`, in key order.
+fn apply_keys(keypad: &mut Keypad, keys: u16) {
+    for key in 0..16 {
+        let down = keys & 1 << key != 0;
+
+        if keypad.pressed[key] != down {
+            keypad.set(key, down);
+        }
+    }
+}
+
+/// Hash of the whole machine state.
+fn state_hash(machine: &Machine) -> u64 {
+    let digest = sha1_smol::Sha1::from(machine.save_state()).digest().bytes();
+    u64::from_le_bytes(digest[..8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asm, debugger::Debugger};
+
+    /// A program whose state depends on the random numbers and on when key 5 is held.
+    fn rom() -> Rom {
+        let program = asm::assemble(
+            "loop: RND V0, 0x3F\nLD V1, 5\nSKNP V1\nADD V2, 1\nLD F, V2\nDRW V0, V0, 5\nJP loop",
+        )
+        .unwrap();
+        Rom::from_bytes(program)
+    }
+
+    /// Record `frames` frames with key 5 held now and then, returning the movie and the final
+    /// state.
+    fn record(rom: &Rom, frames: usize) -> (Movie, Vec<u8>) {
+        let mut machine = Machine::new();
+        machine.load(rom).unwrap();
+        let mut recorder = Recorder::new(&mut machine, rom, 1234);
+
+        for frame in 0..frames {
+            let mut pressed = [false; 16];
+            pressed[5] = frame % 7 < 3;
+
+            recorder.before_frame(&mut machine.keypad, pressed);
+            machine.run_frame().unwrap();
+            recorder.after_frame(&machine);
+        }
+
+        (recorder.finish(), machine.save_state())
+    }
+
+    /// Play `movie` to its end, returning the player and the final state.
+    fn play(movie: Movie, rom: &Rom) -> (Player, Vec<u8>) {
+        let mut machine = movie.machine(rom).unwrap();
+        let mut player = Player::new(movie);
+
+        while !player.finished() {
+            player.before_frame(&mut machine.keypad);
+            machine.run_frame().unwrap();
+            player.after_frame(&machine);
+        }
+
+        (player, machine.save_state())
+    }
+
+    #[test]
+    fn replay_is_deterministic() {
+        let rom = rom();
+        let (movie, recorded) = record(&rom, 150);
+        let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+        assert_eq!(movie.len(), 150);
+
+        let (player, replayed) = play(movie, &rom);
+        assert_eq!(player.desync(), None);
+        assert_eq!(player.frame(), 150);
+        assert_eq!(replayed, recorded);
+    }
+
+    #[test]
+    fn replay_with_another_seed_desyncs() {
+        let rom = rom();
+        let (mut movie, _) = record(&rom, 150);
+        movie.seed += 1;
+
+        let (player, _) = play(movie, &rom);
+        assert_eq!(player.desync(), Some(CHECKPOINT_INTERVAL));
+    }
+
+    #[test]
+    fn movie_of_another_rom_is_refused() {
+        let (movie, _) = record(&rom(), 10);
+
+        assert!(matches!(
+            movie.machine(&Rom::from_bytes(vec![0x12, 0x00])),
+            Err(EmulatorError::MovieRomMismatch)
+        ));
+    }
+
+    #[test]
+    fn pauses_in_the_middle_of_frames_are_not_recorded() {
+        let rom = rom();
+        let mut machine = Machine::new();
+        machine.load(&rom).unwrap();
+        let mut recorder = Recorder::new(&mut machine, &rom, 1234);
+
+        // The breakpoint pauses the machine in the middle of most frames, and the keys change
+        // while it is paused.
+        let mut debugger = Debugger::new();
+        debugger.command(&mut machine, "break 204").unwrap();
+        let mut frames = 0;
+        let mut pauses = 0;
+
+        for tick in 0.. {
+            if frames == 150 {
+                break;
+            }
+
+            if debugger.paused() {
+                pauses += 1;
+                debugger.command(&mut machine, "continue").unwrap();
+            }
+
+            let mut pressed = [false; 16];
+            pressed[5] = tick % 5 < 2;
+
+            recorder.before_frame(&mut machine.keypad, pressed);
+
+            if debugger.run_frame(&mut machine).unwrap() {
+                recorder.after_frame(&machine);
+                frames += 1;
+            }
+        }
+
+        assert!(pauses > 100);
+        let movie = recorder.finish();
+        assert_eq!(movie.len(), 150);
+
+        let (player, replayed) = play(movie, &rom);
+        assert_eq!(player.desync(), None);
+        assert_eq!(replayed, machine.save_state());
+    }
+}
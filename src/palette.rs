@@ -0,0 +1,298 @@
+//! Colours the framebuffer is shown in, and the shape of its pixels.
+
+use std::fmt;
+
+/// An RGB colour.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b }
+    }
+
+    /// This colour `weight` out of 255 of the way from `other`.
+    pub fn mix(self, other: Color, weight: u8) -> Color {
+        let channel = |a: u8, b: u8| {
+            ((a as u32 * weight as u32 + b as u32 * (255 - weight as u32) + 127) / 255) as u8
+        };
+
+        Color::rgb(
+            channel(self.r, other.r),
+            channel(self.g, other.g),
+            channel(self.b, other.b),
+        )
+    }
+
+    /// Parse a colour written as `RRGGBB` or `#RRGGBB` in hexadecimal.
+    pub fn from_hex(hex: &str) -> Option<Color> {
+        let hex = hex.strip_prefix('#').unwrap_or(hex);
+
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+        Some(Color::rgb(channel(0), channel(2), channel(4)))
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02X}{:02X}{:02X}", self.r, self.g, self.b)
+    }
+}
+
+/// The colour of each pixel value of a [`Display`](crate::Display).
+///
+/// Pixel values are bitmasks of the planes a pixel is lit on, so entry 0 is the background,
+/// entry 1 the foreground, and entries 2 and 3 only show up in XO-CHIP programs drawing on
+/// the second plane.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: [Color; 4],
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::PRESETS[0].1
+    }
+}
+
+impl Palette {
+    /// Names accepted by [`Palette::preset`].
+    ///
+    /// `mono` is white on black, `green` and `amber` are the phosphors of monochrome CRT
+    /// monitors, `lcd` is the green-grey of early handheld LCDs, `high-contrast` uses only
+    /// fully saturated colours, and `colorblind` tells the XO-CHIP planes apart with colours
+    /// from the Okabe-Ito set, which stay distinct with every common colour vision deficiency.
+    pub const PRESETS: [(&'static str, Palette); 6] = [
+        (
+            "mono",
+            Palette::new([0x000000, 0xFFFFFF, 0xAAAAAA, 0x555555]),
+        ),
+        (
+            "green",
+            Palette::new([0x001A00, 0x33FF66, 0x1A8033, 0xB3FFC6]),
+        ),
+        (
+            "amber",
+            Palette::new([0x1A0F00, 0xFFB000, 0x996A00, 0xFFD98C]),
+        ),
+        (
+            "lcd",
+            Palette::new([0x9BBC0F, 0x0F380F, 0x8BAC0F, 0x306230]),
+        ),
+        (
+            "high-contrast",
+            Palette::new([0x000000, 0xFFFFFF, 0xFFFF00, 0x00FFFF]),
+        ),
+        (
+            "colorblind",
+            Palette::new([0x000000, 0xFFFFFF, 0xE69F00, 0x56B4E9]),
+        ),
+    ];
+
+    /// A palette of colours written as `0xRRGGBB`.
+    const fn new(colors: [u32; 4]) -> Self {
+        const fn color(rgb: u32) -> Color {
+            Color::rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+        }
+
+        Palette {
+            colors: [
+                color(colors[0]),
+                color(colors[1]),
+                color(colors[2]),
+                color(colors[3]),
+            ],
+        }
+    }
+
+    /// Look up a preset by name.
+    pub fn preset(name: &str) -> Option<Palette> {
+        Palette::PRESETS
+            .iter()
+            .find(|(preset, _)| preset.eq_ignore_ascii_case(name))
+            .map(|&(_, palette)| palette)
+    }
+
+    /// Parse a preset name, or up to 4 colours as `RRGGBB` separated by commas. Colours left
+    /// out are `None`, to be taken from another palette.
+    pub fn partial(value: &str) -> Option<[Option<Color>; 4]> {
+        if let Some(palette) = Palette::preset(value) {
+            return Some(palette.colors.map(Some));
+        }
+
+        let colors = value
+            .split(',')
+            .map(Color::from_hex)
+            .collect::<Option<Vec<_>>>()
+            .filter(|colors| colors.len() <= 4)?;
+
+        let mut palette = [None; 4];
+
+        for (color, configured) in palette.iter_mut().zip(colors) {
+            *color = Some(configured);
+        }
+
+        Some(palette)
+    }
+
+    /// Colour of pixel value `pixel`.
+    pub fn color(&self, pixel: u8) -> Color {
+        self.colors[pixel as usize & 0b11]
+    }
+}
+
+/// Shape pixels are drawn in when scaled up.
+///
+/// Gaps between pixels are in the background colour, and only show up at a scale of 4 screen
+/// pixels per pixel or more.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PixelStyle {
+    /// Squares filling the whole screen.
+    #[default]
+    Square,
+    /// Squares with a gap between them, showing a grid.
+    Grid,
+    /// Round dots, like a matrix of LEDs.
+    Led,
+}
+
+impl PixelStyle {
+    /// Names accepted by [`PixelStyle::from_name`].
+    pub const NAMES: [(&'static str, PixelStyle); 3] = [
+        ("square", PixelStyle::Square),
+        ("grid", PixelStyle::Grid),
+        ("led", PixelStyle::Led),
+    ];
+
+    pub fn from_name(name: &str) -> Option<PixelStyle> {
+        PixelStyle::NAMES
+            .iter()
+            .find(|(style, _)| style.eq_ignore_ascii_case(name))
+            .map(|&(_, style)| style)
+    }
+
+    /// Whether the screen pixel at `x`, `y` of a pixel drawn `scale` screen pixels wide is in
+    /// its colour, rather than in the background colour.
+    pub fn covers(self, scale: u32, x: u32, y: u32) -> bool {
+        let gap = if scale >= 4 { (scale / 8).max(1) } else { 0 };
+        let size = scale - gap;
+
+        match self {
+            PixelStyle::Square => true,
+            _ if x >= size || y >= size => false,
+            PixelStyle::Grid => true,
+            PixelStyle::Led => {
+                // Within the circle filling the gapless part, in half screen pixels.
+                let dx = (2 * x + 1) as i64 - size as i64;
+                let dy = (2 * y + 1) as i64 - size as i64;
+                dx * dx + dy * dy <= (size * size) as i64
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_colors() {
+        assert_eq!(
+            Color::from_hex("1a2B3c"),
+            Some(Color::rgb(0x1A, 0x2B, 0x3C))
+        );
+        assert_eq!(
+            Color::from_hex("#FFB000"),
+            Some(Color::rgb(0xFF, 0xB0, 0x00))
+        );
+        assert_eq!(Color::from_hex("#FFB000").unwrap().to_string(), "#FFB000");
+
+        for hex in [
+            "", "#", "FFF", "#FFFFFFF", "GG0000", "+FFFFF", "##FFFFFF", "FF FF00",
+        ] {
+            assert_eq!(Color::from_hex(hex), None, "{hex:?}");
+        }
+    }
+
+    #[test]
+    fn mixing_at_the_extremes() {
+        let white = Color::rgb(0xFF, 0xFF, 0xFF);
+        let amber = Color::rgb(0xFF, 0xB0, 0x00);
+
+        assert_eq!(amber.mix(white, 255), amber);
+        assert_eq!(amber.mix(white, 0), white);
+        assert_eq!(amber.mix(amber, 100), amber);
+        assert_eq!(
+            white.mix(Color::rgb(0, 0, 0), 128),
+            Color::rgb(128, 128, 128)
+        );
+    }
+
+    #[test]
+    fn presets() {
+        assert_eq!(Palette::default(), Palette::preset("mono").unwrap());
+        assert_eq!(
+            Palette::preset("High-Contrast"),
+            Palette::preset("high-contrast")
+        );
+        assert_eq!(
+            Palette::preset("AMBER").unwrap().color(1),
+            Color::rgb(0xFF, 0xB0, 0x00)
+        );
+        assert_eq!(Palette::preset("sepia"), None);
+
+        for (i, (name, _)) in Palette::PRESETS.iter().enumerate() {
+            assert!(Palette::PRESETS[..i].iter().all(|(other, _)| other != name));
+        }
+    }
+
+    #[test]
+    fn partial_palettes() {
+        let green = Palette::preset("green").unwrap();
+        assert_eq!(Palette::partial("Green"), Some(green.colors.map(Some)));
+
+        assert_eq!(
+            Palette::partial("101010,#33FF66"),
+            Some([
+                Some(Color::rgb(0x10, 0x10, 0x10)),
+                Some(Color::rgb(0x33, 0xFF, 0x66)),
+                None,
+                None,
+            ])
+        );
+        assert_eq!(
+            Palette::partial("000000,111111,222222,333333").map(|colors| colors[3]),
+            Some(Some(Color::rgb(0x33, 0x33, 0x33)))
+        );
+
+        for value in [
+            "",
+            "101010,",
+            "101010,nope",
+            "000000,111111,222222,333333,444444",
+        ] {
+            assert_eq!(Palette::partial(value), None, "{value:?}");
+        }
+    }
+
+    #[test]
+    fn pixel_styles() {
+        assert_eq!(PixelStyle::from_name("LED"), Some(PixelStyle::Led));
+        assert_eq!(PixelStyle::from_name("dots"), None);
+
+        // No gaps below a scale of 4.
+        assert!((0..3).all(|i| PixelStyle::Grid.covers(3, i, i)));
+        assert!(PixelStyle::Grid.covers(8, 6, 6));
+        assert!(!PixelStyle::Grid.covers(8, 7, 0));
+        assert!(!PixelStyle::Led.covers(8, 0, 0));
+        assert!(PixelStyle::Led.covers(8, 3, 3));
+    }
+}
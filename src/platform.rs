@@ -0,0 +1,81 @@
+use crate::{
+    machine::{RAM_SIZE, XO_RAM_SIZE},
+    quirks::Quirks,
+};
+
+/// The CHIP-8 variant a program was written for.
+///
+/// The platform decides which instructions exist and how much memory there is; the finer
+/// behavioural differences are in [`Quirks`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Platform {
+    /// The original CHIP-8.
+    #[default]
+    Chip8,
+    /// SUPER-CHIP 1.1, adding a 128x64 hi-res mode, scrolling and large sprites.
+    SuperChip,
+    /// XO-CHIP, extending SUPER-CHIP with 64 KiB of memory and a second bitplane.
+    XoChip,
+}
+
+impl Platform {
+    /// Names accepted by [`Platform::from_name`], with the platform they select.
+    pub const NAMES: [(&'static str, Platform); 3] = [
+        ("chip8", Platform::Chip8),
+        ("schip", Platform::SuperChip),
+        ("xochip", Platform::XoChip),
+    ];
+
+    /// Look up a platform by name.
+    pub fn from_name(name: &str) -> Option<Platform> {
+        Platform::NAMES
+            .iter()
+            .find(|(platform, _)| platform.eq_ignore_ascii_case(name))
+            .map(|&(_, platform)| platform)
+    }
+
+    /// The platform conventionally implied by a ROM file extension: `ch8`, `sc8` or `xo8`.
+    pub fn from_extension(extension: &str) -> Option<Platform> {
+        match extension.to_ascii_lowercase().as_str() {
+            "ch8" => Some(Platform::Chip8),
+            "sc8" => Some(Platform::SuperChip),
+            "xo8" => Some(Platform::XoChip),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        Platform::NAMES
+            .iter()
+            .find(|(_, platform)| platform == self)
+            .map(|&(name, _)| name)
+            .unwrap()
+    }
+
+    /// The quirks programs written for this platform usually expect.
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Platform::Chip8 => Quirks::COSMAC_VIP,
+            Platform::SuperChip => Quirks::SUPER_CHIP,
+            Platform::XoChip => Quirks::XO_CHIP,
+        }
+    }
+
+    /// Size of the addressable memory in bytes.
+    pub fn ram_size(&self) -> usize {
+        match self {
+            Platform::Chip8 | Platform::SuperChip => RAM_SIZE,
+            Platform::XoChip => XO_RAM_SIZE,
+        }
+    }
+
+    /// Whether the SUPER-CHIP instructions are available.
+    pub fn super_chip(&self) -> bool {
+        *self != Platform::Chip8
+    }
+
+    /// Whether the XO-CHIP instructions are available.
+    pub fn xo_chip(&self) -> bool {
+        *self == Platform::XoChip
+    }
+}
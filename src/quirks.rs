@@ -0,0 +1,178 @@
+use crate::{
+    error::EmulatorError,
+    state::{Reader, Writer},
+};
+
+/// Where `Fx55`/`Fx65` leave I after storing or loading V0 through Vx.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryIncrement {
+    /// I is left unchanged, as on SUPER-CHIP 1.1.
+    Unchanged,
+    /// I is increased by x, pointing at the last register, as on CHIP-48.
+    ByX,
+    /// I is increased by x + 1, pointing past the last register, as on the COSMAC VIP.
+    PastX,
+}
+
+impl MemoryIncrement {
+    /// How much I is increased by for `Fx55`/`Fx65`.
+    pub fn amount(&self, x: usize) -> u16 {
+        match self {
+            MemoryIncrement::Unchanged => 0,
+            MemoryIncrement::ByX => x as u16,
+            MemoryIncrement::PastX => x as u16 + 1,
+        }
+    }
+}
+
+/// Behaviours that differ between CHIP-8 interpreters.
+///
+/// Each flag switches one instruction between its original COSMAC VIP meaning and the one
+/// later interpreters gave it. Programs written for one interpreter often misbehave on
+/// another, so pick the preset matching the platform a ROM was written for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    /// `8xy6`/`8xyE` shift Vx in place and ignore Vy, instead of shifting Vy into Vx.
+    pub shift: bool,
+    /// Where `Fx55`/`Fx65` leave I.
+    pub memory_increment: MemoryIncrement,
+    /// `Bnnn` is decoded as `Bxnn` and jumps to xnn + Vx instead of nnn + V0.
+    pub jump: bool,
+    /// `8xy1`/`8xy2`/`8xy3` reset VF to 0.
+    pub vf_reset: bool,
+    /// Sprites are clipped at the screen edges instead of wrapping around.
+    pub clip: bool,
+    /// `Dxyn` waits for the next frame, limiting drawing to one sprite per frame.
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    /// The original interpreter on the RCA COSMAC VIP.
+    pub const COSMAC_VIP: Quirks = Quirks {
+        shift: false,
+        memory_increment: MemoryIncrement::PastX,
+        jump: false,
+        vf_reset: true,
+        clip: true,
+        display_wait: true,
+    };
+
+    /// CHIP-48 on the HP-48 calculators.
+    pub const CHIP_48: Quirks = Quirks {
+        shift: true,
+        memory_increment: MemoryIncrement::ByX,
+        jump: true,
+        vf_reset: false,
+        clip: true,
+        display_wait: false,
+    };
+
+    /// SUPER-CHIP 1.1 on the HP-48 calculators.
+    pub const SUPER_CHIP: Quirks = Quirks {
+        shift: true,
+        memory_increment: MemoryIncrement::Unchanged,
+        jump: true,
+        vf_reset: false,
+        clip: true,
+        display_wait: false,
+    };
+
+    /// XO-CHIP, as implemented by Octo.
+    pub const XO_CHIP: Quirks = Quirks {
+        shift: false,
+        memory_increment: MemoryIncrement::PastX,
+        jump: false,
+        vf_reset: false,
+        clip: false,
+        display_wait: false,
+    };
+
+    /// Names accepted by [`Quirks::preset`], with the preset they select.
+    pub const PRESETS: [(&'static str, Quirks); 4] = [
+        ("vip", Quirks::COSMAC_VIP),
+        ("chip48", Quirks::CHIP_48),
+        ("schip", Quirks::SUPER_CHIP),
+        ("xochip", Quirks::XO_CHIP),
+    ];
+
+    /// Look up a preset by name.
+    pub fn preset(name: &str) -> Option<Quirks> {
+        Quirks::PRESETS
+            .iter()
+            .find(|(preset, _)| preset.eq_ignore_ascii_case(name))
+            .map(|&(_, quirks)| quirks)
+    }
+
+    pub(crate) fn save(&self, writer: &mut Writer) {
+        writer.bool(self.shift);
+        writer.u8(match self.memory_increment {
+            MemoryIncrement::Unchanged => 0,
+            MemoryIncrement::PastX => 1,
+            MemoryIncrement::ByX => 2,
+        });
+        writer.bool(self.jump);
+        writer.bool(self.vf_reset);
+        writer.bool(self.clip);
+        writer.bool(self.display_wait);
+    }
+
+    pub(crate) fn load(reader: &mut Reader) -> Result<Self, EmulatorError> {
+        Ok(Quirks {
+            shift: reader.bool()?,
+            memory_increment: match reader.u8()? {
+                0 => MemoryIncrement::Unchanged,
+                1 => MemoryIncrement::PastX,
+                2 => MemoryIncrement::ByX,
+                _ => return Err(EmulatorError::CorruptState),
+            },
+            jump: reader.bool()?,
+            vf_reset: reader.bool()?,
+            clip: reader.bool()?,
+            display_wait: reader.bool()?,
+        })
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks::COSMAC_VIP
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{MAGIC, VERSION};
+
+    #[test]
+    fn presets_by_name() {
+        assert_eq!(Quirks::preset("VIP"), Some(Quirks::COSMAC_VIP));
+        assert_eq!(Quirks::preset("chip48"), Some(Quirks::CHIP_48));
+        assert_eq!(Quirks::preset("schip"), Some(Quirks::SUPER_CHIP));
+        assert_eq!(Quirks::preset("XoChip"), Some(Quirks::XO_CHIP));
+        assert_eq!(Quirks::preset("octo"), None);
+        assert_eq!(Quirks::default(), Quirks::COSMAC_VIP);
+    }
+
+    #[test]
+    fn presets_differ() {
+        for (i, (name, quirks)) in Quirks::PRESETS.iter().enumerate() {
+            for (other, other_quirks) in &Quirks::PRESETS[i + 1..] {
+                assert_ne!(quirks, other_quirks, "{} and {}", name, other);
+            }
+        }
+    }
+
+    #[test]
+    fn save_and_load() {
+        for (_, quirks) in Quirks::PRESETS {
+            let mut writer = Writer::new(MAGIC, VERSION);
+            quirks.save(&mut writer);
+            let bytes = writer.finish();
+
+            let mut reader = Reader::new(&bytes, MAGIC, VERSION).unwrap();
+            assert_eq!(Quirks::load(&mut reader).unwrap(), quirks);
+            reader.finish().unwrap();
+        }
+    }
+}
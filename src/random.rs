@@ -0,0 +1,237 @@
+//! Random number generators for `Cxkk`.
+//!
+//! The generator is a part of the [`Machine`](crate::Machine) like any other: it is seeded
+//! explicitly, saved in save states and can be replaced by another implementation of
+//! [`Random`], so runs involving random numbers can be reproduced.
+
+use crate::machine::PROGRAM_START;
+
+/// A source of random bytes for `Cxkk`.
+pub trait Random: Send {
+    /// Name of the generator, as accepted by [`from_name`] for the built-in ones.
+    fn name(&self) -> &'static str;
+
+    /// Restart the sequence from `seed`.
+    fn seed(&mut self, seed: u64);
+
+    /// Next random byte. `ram` is the memory of the machine drawing it.
+    fn byte(&mut self, ram: &[u8]) -> u8;
+
+    /// Called for every instruction the machine executes.
+    fn step(&mut self) {}
+
+    /// The internal state, for save states.
+    fn state(&self) -> Vec<u8>;
+
+    /// Restore a state returned by [`Random::state`]. Returns `false`, leaving the generator
+    /// untouched, if `state` doesn't belong to this kind of generator.
+    fn restore(&mut self, state: &[u8]) -> bool;
+}
+
+/// Names of the built-in generators, accepted by [`from_name`].
+pub const NAMES: [&str; 2] = [Xorshift::NAME, Counter::NAME];
+
+/// Create the built-in generator called `name`, seeded with `seed`.
+pub fn from_name(name: &str, seed: u64) -> Option<Box<dyn Random>> {
+    let mut random: Box<dyn Random> = if name.eq_ignore_ascii_case(Xorshift::NAME) {
+        Box::new(Xorshift::new())
+    } else if name.eq_ignore_ascii_case(Counter::NAME) {
+        Box::new(Counter::new())
+    } else {
+        return None;
+    };
+
+    random.seed(seed);
+    Some(random)
+}
+
+/// The default generator: xorshift64*, fast and with no visible patterns.
+pub struct Xorshift {
+    state: u64,
+}
+
+impl Default for Xorshift {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Xorshift {
+    const NAME: &'static str = "xorshift";
+
+    /// Create a generator with a random seed.
+    pub fn new() -> Self {
+        let mut random = Xorshift { state: 0 };
+        random.seed(rand::random());
+        random
+    }
+}
+
+impl Random for Xorshift {
+    fn name(&self) -> &'static str {
+        Xorshift::NAME
+    }
+
+    fn seed(&mut self, seed: u64) {
+        // Spread the seed with SplitMix64, so that close seeds give unrelated sequences, and
+        // avoid the all-zero state xorshift can't leave.
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        self.state = (z ^ (z >> 31)).max(1);
+    }
+
+    fn byte(&mut self, _ram: &[u8]) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+
+    fn state(&self) -> Vec<u8> {
+        self.state.to_le_bytes().to_vec()
+    }
+
+    fn restore(&mut self, state: &[u8]) -> bool {
+        match state.try_into() {
+            Ok(state) if u64::from_le_bytes(state) != 0 => {
+                self.state = u64::from_le_bytes(state);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A deliberately weak generator in the style of early interpreters such as the COSMAC VIP's.
+///
+/// A 16-bit counter advances with every instruction. Drawing a number adds the low byte of
+/// the counter to a byte of the program picked by its high byte, and the sum becomes the new
+/// high byte. As with those interpreters, the numbers depend on how many instructions ran
+/// since the last draw, and programs drawing in a tight loop see short repeating runs.
+///
+/// This only imitates the character of the VIP routine, which reads the interpreter's own
+/// code; the sequences are not the ones real hardware gives.
+pub struct Counter {
+    counter: u16,
+}
+
+impl Default for Counter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Counter {
+    const NAME: &'static str = "counter";
+
+    /// Create a generator with a random seed.
+    pub fn new() -> Self {
+        Counter {
+            counter: rand::random(),
+        }
+    }
+}
+
+impl Random for Counter {
+    fn name(&self) -> &'static str {
+        Counter::NAME
+    }
+
+    fn seed(&mut self, seed: u64) {
+        self.counter = seed as u16;
+    }
+
+    fn byte(&mut self, ram: &[u8]) -> u8 {
+        let [low, high] = self.counter.to_le_bytes();
+        let code = ram[(PROGRAM_START + high as usize) % ram.len()];
+        let high = code.wrapping_add(low);
+        self.counter = u16::from_le_bytes([low, high]);
+        high
+    }
+
+    fn step(&mut self) {
+        self.counter = self.counter.wrapping_add(1);
+    }
+
+    fn state(&self) -> Vec<u8> {
+        self.counter.to_le_bytes().to_vec()
+    }
+
+    fn restore(&mut self, state: &[u8]) -> bool {
+        match state.try_into() {
+            Ok(state) => {
+                self.counter = u16::from_le_bytes(state);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `count` numbers from `random`, stepping it a few times between draws.
+    fn draw(random: &mut dyn Random, count: usize) -> Vec<u8> {
+        let ram: Vec<u8> = (0..4096).map(|address| (address * 7) as u8).collect();
+
+        (0..count)
+            .map(|_| {
+                for _ in 0..3 {
+                    random.step();
+                }
+
+                random.byte(&ram)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_same_numbers() {
+        for name in NAMES {
+            let first = draw(&mut *from_name(name, 42).unwrap(), 100);
+            let second = draw(&mut *from_name(name, 42).unwrap(), 100);
+            let other = draw(&mut *from_name(name, 43).unwrap(), 100);
+
+            assert_eq!(first, second, "{}", name);
+            assert_ne!(first, other, "{}", name);
+        }
+    }
+
+    #[test]
+    fn state_round_trip() {
+        for name in NAMES {
+            let mut random = from_name(name, 7).unwrap();
+            draw(&mut *random, 10);
+            let state = random.state();
+            let expected = draw(&mut *random, 10);
+
+            let mut restored = from_name(name, 99).unwrap();
+            assert!(restored.restore(&state));
+            assert_eq!(draw(&mut *restored, 10), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn state_of_another_generator_is_refused() {
+        let mut xorshift = from_name(Xorshift::NAME, 1).unwrap();
+        let mut counter = from_name(Counter::NAME, 1).unwrap();
+        let (xorshift_state, counter_state) = (xorshift.state(), counter.state());
+
+        assert!(!xorshift.restore(&counter_state));
+        assert!(!counter.restore(&xorshift_state));
+        assert_eq!(xorshift.state(), xorshift_state);
+        assert_eq!(counter.state(), counter_state);
+    }
+
+    #[test]
+    fn xorshift_refuses_the_zero_state() {
+        let mut random = Xorshift::new();
+        let state = random.state();
+
+        assert!(!random.restore(&[0; 8]));
+        assert_eq!(random.state(), state);
+    }
+}
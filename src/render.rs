@@ -0,0 +1,137 @@
+//! Drawing the framebuffer into an RGB image, in software, so that a window and a headless
+//! run show the same thing.
+
+use crate::{
+    palette::{Palette, PixelStyle},
+    video::Display,
+};
+
+/// An RGB image, 3 bytes per pixel, row by row from the top left.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub rgb: Vec<u8>,
+}
+
+/// Draws a [`Display`] in a palette and pixel style, keeping its buffers from one frame to the
+/// next. Pixels show as [`Display::shade`] says, fading or blended with its persistence.
+pub struct Renderer {
+    pub palette: Palette,
+    pub pixels: PixelStyle,
+    image: Image,
+    /// Which image pixels of a pixel are in its colour, row by row, at the scale they were
+    /// worked out for.
+    shape: (usize, Vec<bool>),
+}
+
+impl Renderer {
+    pub fn new(palette: Palette, pixels: PixelStyle) -> Self {
+        Renderer {
+            palette,
+            pixels,
+            image: Image::default(),
+            shape: (0, Vec::new()),
+        }
+    }
+
+    /// Draw `display` at its current resolution, each pixel `scale` image pixels wide.
+    pub fn render(&mut self, display: &Display, scale: usize) -> &Image {
+        let scale = scale.max(1);
+
+        if self.shape.0 != scale {
+            let pixels = self.pixels;
+            let covered = (0..scale * scale)
+                .map(|i| pixels.covers(scale as u32, (i % scale) as u32, (i / scale) as u32))
+                .collect();
+            self.shape = (scale, covered);
+        }
+
+        let image = &mut self.image;
+        image.width = display.width() * scale;
+        image.height = display.height() * scale;
+        image.rgb.resize(image.width * image.height * 3, 0);
+
+        let background = self.palette.color(0);
+        let shape = &self.shape.1;
+
+        for y in 0..display.height() {
+            for x in 0..display.width() {
+                let shade = display.shade(x, y);
+                let color = self
+                    .palette
+                    .color(shade.pixel)
+                    .mix(self.palette.color(shade.behind), shade.weight);
+
+                for (i, &covered) in shape.iter().enumerate() {
+                    let color = if covered { color } else { background };
+                    let at = ((y * scale + i / scale) * image.width + x * scale + i % scale) * 3;
+                    image.rgb[at..at + 3].copy_from_slice(&[color.r, color.g, color.b]);
+                }
+            }
+        }
+
+        image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::palette::Color;
+
+    /// A lo-res display with only its top left pixel lit.
+    fn corner() -> Display {
+        let mut display = Display::new();
+        display.set(1, 0, 0, true);
+        display
+    }
+
+    fn pixel(image: &Image, x: usize, y: usize) -> Color {
+        let at = (y * image.width + x) * 3;
+        Color::rgb(image.rgb[at], image.rgb[at + 1], image.rgb[at + 2])
+    }
+
+    #[test]
+    fn image_size_follows_resolution_and_scale() {
+        let mut renderer = Renderer::new(Palette::default(), PixelStyle::Square);
+        let mut display = Display::new();
+
+        let image = renderer.render(&display, 3);
+        assert_eq!((image.width, image.height), (192, 96));
+        assert_eq!(image.rgb.len(), 192 * 96 * 3);
+
+        display.set_hires(true);
+        let image = renderer.render(&display, 0);
+        assert_eq!((image.width, image.height), (128, 64));
+        assert_eq!(image.rgb.len(), 128 * 64 * 3);
+    }
+
+    #[test]
+    fn pixel_styles_at_scale_8() {
+        let palette = Palette::default();
+        let (off, on) = (palette.color(0), palette.color(1));
+        let display = corner();
+
+        let mut square = Renderer::new(palette, PixelStyle::Square);
+        let image = square.render(&display, 8);
+        assert_eq!(pixel(image, 0, 0), on);
+        assert_eq!(pixel(image, 7, 7), on);
+        assert_eq!(pixel(image, 8, 0), off);
+
+        let mut grid = Renderer::new(palette, PixelStyle::Grid);
+        let image = grid.render(&display, 8);
+        assert_eq!(pixel(image, 0, 0), on);
+        assert_eq!(pixel(image, 6, 6), on);
+        assert_eq!(pixel(image, 7, 0), off);
+        assert_eq!(pixel(image, 0, 7), off);
+
+        let mut led = Renderer::new(palette, PixelStyle::Led);
+        let image = led.render(&display, 8);
+        assert_eq!(pixel(image, 3, 3), on);
+        assert_eq!(pixel(image, 3, 0), on);
+        assert_eq!(pixel(image, 0, 0), off);
+        assert_eq!(pixel(image, 6, 6), off);
+        assert_eq!(pixel(image, 7, 3), off);
+    }
+}
@@ -0,0 +1,273 @@
+//! Rewinding: a ring of recent save states, replayed backwards.
+
+use std::collections::VecDeque;
+
+use crate::machine::Machine;
+
+/// Default memory budget of a [`Rewind`] buffer, in bytes.
+pub const DEFAULT_MEMORY: usize = 16 * 1024 * 1024;
+/// Default number of frames between snapshots.
+pub const DEFAULT_INTERVAL: u32 = 2;
+
+/// Snapshots of a machine taken every few frames, to step back through them.
+///
+/// Only the newest snapshot is kept whole. Every older one is stored as the difference to the
+/// snapshot after it, so a frame in which little changed costs a few bytes rather than a full
+/// state. Once the buffer grows past its memory budget, the oldest snapshots are dropped.
+pub struct Rewind {
+    memory: usize,
+    interval: u32,
+    frames: u32,
+
+    newest: Option<Vec<u8>>,
+    /// Differences to older snapshots, oldest first.
+    deltas: VecDeque<Vec<u8>>,
+    used: usize,
+}
+
+impl Default for Rewind {
+    fn default() -> Self {
+        Self::new(DEFAULT_MEMORY, DEFAULT_INTERVAL)
+    }
+}
+
+impl Rewind {
+    /// Create an empty buffer using at most about `memory` bytes, snapshotting every
+    /// `interval` frames.
+    pub fn new(memory: usize, interval: u32) -> Self {
+        Rewind {
+            memory,
+            interval: interval.max(1),
+            frames: 0,
+            newest: None,
+            deltas: VecDeque::new(),
+            used: 0,
+        }
+    }
+
+    /// Number of snapshots held.
+    pub fn len(&self) -> usize {
+        self.newest.as_ref().map_or(0, |_| self.deltas.len() + 1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.newest.is_none()
+    }
+
+    /// Bytes currently used by the snapshots.
+    pub fn memory_used(&self) -> usize {
+        self.used
+    }
+
+    /// Forget every snapshot.
+    pub fn clear(&mut self) {
+        self.newest = None;
+        self.deltas.clear();
+        self.used = 0;
+        self.frames = 0;
+    }
+
+    /// Call after every frame the machine runs; snapshots it once every interval.
+    pub fn record_frame(&mut self, machine: &Machine) {
+        if self.frames == 0 {
+            self.push(machine.save_state());
+        }
+
+        self.frames = (self.frames + 1) % self.interval;
+    }
+
+    /// Call instead of running the machine for every frame spent rewinding. Steps back one
+    /// snapshot every interval, so the game plays backwards at its normal speed. Returns
+    /// `false` once the oldest snapshot is reached.
+    pub fn rewind_frame(&mut self, machine: &mut Machine) -> bool {
+        let due = self.frames == 0;
+        self.frames = (self.frames + 1) % self.interval;
+
+        let Some(newest) = &mut self.newest else {
+            return false;
+        };
+
+        if !due {
+            return true;
+        }
+
+        // The states are our own, so they are always readable.
+        machine.load_state(newest).unwrap();
+
+        // Step the newest snapshot back to the one before, unless this was the oldest, which
+        // stays to be restored again.
+        let Some(delta) = self.deltas.pop_back() else {
+            return false;
+        };
+
+        self.used -= delta.len();
+        apply_delta(newest, &delta);
+        true
+    }
+
+    fn push(&mut self, state: Vec<u8>) {
+        if let Some(previous) = self.newest.take() {
+            self.used -= previous.len();
+
+            if previous.len() == state.len() {
+                let delta = delta(&state, &previous);
+                self.used += delta.len();
+                self.deltas.push_back(delta);
+            } else {
+                // A state of another platform, there is nothing to go back to from it.
+                self.clear();
+            }
+        }
+
+        self.used += state.len();
+        self.newest = Some(state);
+
+        while self.used > self.memory {
+            match self.deltas.pop_front() {
+                Some(delta) => self.used -= delta.len(),
+                None => break,
+            }
+        }
+    }
+}
+
+/// Encode the difference turning `from` into `to`, two states of the same length.
+///
+/// The bytes are XORed and the result run-length encoded as pairs of runs: a number of
+/// unchanged bytes, then a number of changed bytes followed by their XOR. Counts are LEB128.
+fn delta(from: &[u8], to: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    let mut position = 0;
+
+    while position < from.len() {
+        let unchanged = (position..from.len())
+            .find(|&i| from[i] != to[i])
+            .unwrap_or(from.len());
+        let changed = (unchanged..from.len())
+            .find(|&i| from[i] == to[i])
+            .unwrap_or(from.len());
+
+        write_count(&mut encoded, unchanged - position);
+        write_count(&mut encoded, changed - unchanged);
+        encoded.extend((unchanged..changed).map(|i| from[i] ^ to[i]));
+
+        position = changed;
+    }
+
+    encoded
+}
+
+/// Apply a difference made by [`delta`] to `state`.
+fn apply_delta(state: &mut [u8], delta: &[u8]) {
+    let mut delta = delta.iter().copied();
+    let mut position = 0;
+
+    while let Some(unchanged) = read_count(&mut delta) {
+        position += unchanged;
+        let changed = read_count(&mut delta).unwrap_or(0);
+
+        for byte in &mut state[position..position + changed] {
+            *byte ^= delta.next().unwrap_or(0);
+        }
+
+        position += changed;
+    }
+}
+
+fn write_count(encoded: &mut Vec<u8>, mut count: usize) {
+    while count >= 0x80 {
+        encoded.push(count as u8 | 0x80);
+        count >>= 7;
+    }
+
+    encoded.push(count as u8);
+}
+
+fn read_count<I: Iterator<Item = u8>>(delta: &mut I) -> Option<usize> {
+    let mut count = 0;
+    let mut shift = 0;
+
+    loop {
+        let byte = delta.next()?;
+        count |= ((byte & 0x7F) as usize) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return Some(count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asm, rom::Rom};
+
+    /// A machine counting frames in V0 and drawing the count, so that every frame changes
+    /// the state a little.
+    fn machine() -> Machine {
+        let program = asm::assemble("loop: ADD V0, 1\nLD F, V0\nDRW V1, V1, 5\nJP loop").unwrap();
+        let mut machine = Machine::new();
+        machine.instructions_per_frame = 4;
+        machine.load(&Rom::from_bytes(program)).unwrap();
+        machine
+    }
+
+    #[test]
+    fn delta_round_trip() {
+        let from: Vec<u8> = (0..300).map(|i| (i * 7) as u8).collect();
+        let mut to = from.clone();
+        to[0] ^= 1;
+        to[150..290].fill(0xAA);
+        to[299] = 3;
+
+        let encoded = delta(&from, &to);
+        assert!(encoded.len() < from.len());
+
+        let mut state = to.clone();
+        apply_delta(&mut state, &encoded);
+        assert_eq!(state, from);
+
+        // 300 unchanged bytes in LEB128, then no changed ones.
+        assert_eq!(delta(&from, &from), [0xAC, 0x02, 0]);
+    }
+
+    #[test]
+    fn rewinds_through_every_snapshot() {
+        let mut machine = machine();
+        let mut rewind = Rewind::new(DEFAULT_MEMORY, 1);
+        let mut states = Vec::new();
+
+        for _ in 0..20 {
+            machine.run_frame().unwrap();
+            rewind.record_frame(&machine);
+            states.push(machine.save_state());
+        }
+
+        assert_eq!(rewind.len(), 20);
+
+        for state in states[1..].iter().rev() {
+            assert!(rewind.rewind_frame(&mut machine));
+            assert_eq!(&machine.save_state(), state);
+        }
+
+        assert!(!rewind.rewind_frame(&mut machine));
+        assert_eq!(machine.save_state(), states[0]);
+    }
+
+    #[test]
+    fn drops_the_oldest_snapshots_past_the_budget() {
+        let mut machine = machine();
+        let state_size = machine.save_state().len();
+        let mut rewind = Rewind::new(state_size + 100, 2);
+
+        for _ in 0..200 {
+            machine.run_frame().unwrap();
+            rewind.record_frame(&machine);
+        }
+
+        assert!(rewind.len() > 1);
+        assert!(rewind.len() < 100);
+        assert!(rewind.memory_used() <= state_size + 100);
+    }
+}
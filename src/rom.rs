@@ -0,0 +1,35 @@
+use std::{fs, path::Path};
+
+use crate::error::EmulatorError;
+
+/// A program image, as loaded from a `.ch8` file.
+pub struct Rom {
+    bytes: Vec<u8>,
+}
+
+impl Rom {
+    /// Read a ROM from disk.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, EmulatorError> {
+        fs::read(path)
+            .map(Rom::from_bytes)
+            .map_err(EmulatorError::RomIo)
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Rom { bytes }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// SHA-1 digest of the ROM, identifying it whatever the file is called.
+    pub fn sha1(&self) -> [u8; 20] {
+        sha1_smol::Sha1::from(&self.bytes).digest().bytes()
+    }
+
+    /// [`Rom::sha1`] in lowercase hexadecimal.
+    pub fn sha1_hex(&self) -> String {
+        sha1_smol::Sha1::from(&self.bytes).digest().to_string()
+    }
+}
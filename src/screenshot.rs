@@ -0,0 +1,151 @@
+//! Screenshots: the display saved as PNG images, as it is shown with the palette, pixel style
+//! and persistence of a [`Renderer`].
+//!
+//! A screenshot is saved twice, at the resolution of the display and scaled up as in the
+//! window, in files named after the ROM and the local time it was taken, such as
+//! `pong-20240131-154502.png` and `pong-20240131-154502-x20.png`.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use chrono::Local;
+
+use crate::{
+    error::EmulatorError,
+    render::{Image, Renderer},
+    video::Display,
+};
+
+/// Where screenshots of a ROM go, and what they are called.
+#[derive(Clone, Debug)]
+pub struct Screenshots {
+    dir: PathBuf,
+    name: String,
+}
+
+impl Screenshots {
+    /// Screenshots of the ROM file at `rom`, saved in `dir`, which is created if needed.
+    pub fn new<P: AsRef<Path>>(dir: PathBuf, rom: P) -> Self {
+        let name = rom.as_ref().file_stem().map_or_else(
+            || "chip8".into(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+
+        Screenshots { dir, name }
+    }
+
+    /// Save `display` as `renderer` draws it, at its resolution and, if `scale` is more than 1,
+    /// with every pixel `scale` pixels wide. Returns the files written.
+    pub fn take(
+        &self,
+        renderer: &mut Renderer,
+        display: &Display,
+        scale: usize,
+    ) -> Result<Vec<PathBuf>, EmulatorError> {
+        fs::create_dir_all(&self.dir).map_err(EmulatorError::ScreenshotIo)?;
+
+        let stem = self.free_stem();
+        let mut paths = vec![self.dir.join(format!("{}.png", stem))];
+
+        if scale > 1 {
+            paths.push(self.dir.join(format!("{}-x{}.png", stem, scale)));
+        }
+
+        for (path, scale) in paths.iter().zip([1, scale]) {
+            save_png(renderer.render(display, scale), path)?;
+        }
+
+        Ok(paths)
+    }
+
+    /// A file name without extension that no screenshot has yet, from the ROM name and the
+    /// time, numbered if several are taken within a second.
+    fn free_stem(&self) -> String {
+        let stem = format!("{}-{}", self.name, Local::now().format("%Y%m%d-%H%M%S"));
+
+        (1..)
+            .map(|n| match n {
+                1 => stem.clone(),
+                n => format!("{}-{}", stem, n),
+            })
+            .find(|stem| !self.dir.join(format!("{}.png", stem)).exists())
+            .unwrap()
+    }
+}
+
+/// Write `image` to a PNG file at `path`.
+pub fn save_png<P: AsRef<Path>>(image: &Image, path: P) -> Result<(), EmulatorError> {
+    let io = |err: png::EncodingError| {
+        EmulatorError::ScreenshotIo(match err {
+            png::EncodingError::IoError(err) => err,
+            err => io::Error::other(err),
+        })
+    };
+
+    let file = File::create(path).map_err(EmulatorError::ScreenshotIo)?;
+
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(file),
+        image.width as u32,
+        image.height as u32,
+    );
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(io)?;
+    writer.write_image_data(&image.rgb).map_err(io)?;
+    writer.finish().map_err(io)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, io::BufReader, process};
+
+    use super::*;
+    use crate::palette::{Palette, PixelStyle};
+
+    /// Width and height of the PNG image at `path`.
+    fn size(path: &Path) -> (u32, u32) {
+        let decoder = png::Decoder::new(BufReader::new(File::open(path).unwrap()));
+        let reader = decoder.read_info().unwrap();
+        (reader.info().width, reader.info().height)
+    }
+
+    fn stem(path: &Path) -> String {
+        path.file_stem().unwrap().to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn take_saves_both_sizes_and_numbers_repeats() {
+        let dir = env::temp_dir().join(format!("chip8-screenshots-{}", process::id()));
+        let screenshots = Screenshots::new(dir.clone(), "roms/Pong.ch8");
+        let mut renderer = Renderer::new(Palette::default(), PixelStyle::Grid);
+        let mut display = Display::new();
+        display.set(1, 0, 0, true);
+
+        // Try again if the clock ticks over between the two screenshots.
+        let (first, second) = (0..5)
+            .find_map(|_| {
+                let _ = fs::remove_dir_all(&dir);
+                let first = screenshots.take(&mut renderer, &display, 4).unwrap();
+                let second = screenshots.take(&mut renderer, &display, 1).unwrap();
+                let same_second = stem(&second[0]).starts_with(&stem(&first[0]));
+                same_second.then_some((first, second))
+            })
+            .unwrap();
+
+        let stem = stem(&first[0]);
+        assert!(stem.starts_with("Pong-"));
+        assert_eq!(first[1], dir.join(format!("{}-x4.png", stem)));
+        assert_eq!(second, [dir.join(format!("{}-2.png", stem))]);
+
+        assert_eq!(size(&first[0]), (64, 32));
+        assert_eq!(size(&first[1]), (256, 128));
+        assert_eq!(size(&second[0]), (64, 32));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
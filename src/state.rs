@@ -0,0 +1,166 @@
+//! Save states: the complete machine state in a versioned binary format.
+//!
+//! A state is [`MAGIC`], the format version as a little-endian `u16`, then the fields of the
+//! machine in a fixed order, written by [`Machine::save_state`]. Multi-byte integers are
+//! little-endian and booleans are a single byte.
+//!
+//! States written by an older version are migrated when loaded. A newer or unknown version is
+//! refused with [`EmulatorError::UnsupportedState`], and a truncated or damaged state with
+//! [`EmulatorError::CorruptState`].
+//!
+//! Versions:
+//!
+//! 1. The first version.
+//! 2. Adds the name and state of the random number generator at the end. Version 1 states
+//!    keep the generator the machine had before loading them.
+//! 3. `Fx0A` waits for a key to be pressed and then released: the keypad keeps the last key
+//!    pressed instead of the last released, and the key being waited for follows the
+//!    register it goes to. Version 2 states waiting for a key wait for a new press.
+//! 4. Adds whether an XO-CHIP audio pattern was loaded, before the pattern. Older states count
+//!    a pattern as loaded unless it is silent.
+//! 5. The memory increment quirk can be 2 as well as a boolean, for `Fx55`/`Fx65` increasing I
+//!    by x as on CHIP-48. Older states read the same.
+
+use std::{fs, path::Path};
+
+use crate::{error::EmulatorError, machine::Machine};
+
+/// Bytes every save state starts with.
+pub const MAGIC: [u8; 4] = *b"C8ST";
+/// Format version written by this build.
+pub const VERSION: u16 = 5;
+
+/// Save the state of `machine` to the file at `path`.
+pub fn save_file<P: AsRef<Path>>(machine: &Machine, path: P) -> Result<(), EmulatorError> {
+    fs::write(path, machine.save_state()).map_err(EmulatorError::StateIo)
+}
+
+/// Restore `machine` from the state in the file at `path`.
+pub fn load_file<P: AsRef<Path>>(machine: &mut Machine, path: P) -> Result<(), EmulatorError> {
+    let state = fs::read(path).map_err(EmulatorError::StateIo)?;
+    machine.load_state(&state)
+}
+
+/// Appends fields to a state, or any other file in the same style.
+pub(crate) struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    /// Start a file with `magic` and format `version`.
+    pub(crate) fn new(magic: [u8; 4], version: u16) -> Self {
+        let mut writer = Writer { bytes: Vec::new() };
+        writer.bytes(&magic);
+        writer.u16(version);
+        writer
+    }
+
+    pub(crate) fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes)
+    }
+
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.bytes.push(value)
+    }
+
+    pub(crate) fn bool(&mut self, value: bool) {
+        self.u8(value as u8)
+    }
+
+    pub(crate) fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_le_bytes())
+    }
+}
+
+/// Reads fields back from a [`Writer`], failing with [`EmulatorError::CorruptState`] past the
+/// end.
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    /// Format version of the file being read.
+    pub(crate) version: u16,
+}
+
+impl<'a> Reader<'a> {
+    /// Check that `bytes` start with `magic` and a version up to `version`, and position the
+    /// reader after them.
+    pub(crate) fn new(
+        bytes: &'a [u8],
+        magic: [u8; 4],
+        version: u16,
+    ) -> Result<Self, EmulatorError> {
+        let mut reader = Reader { bytes, version: 0 };
+
+        if reader.bytes(magic.len())? != magic {
+            return Err(EmulatorError::CorruptState);
+        }
+
+        reader.version = reader.u16()?;
+
+        if reader.version == 0 || reader.version > version {
+            return Err(EmulatorError::UnsupportedState {
+                version: reader.version,
+            });
+        }
+
+        Ok(reader)
+    }
+
+    /// Fail unless every byte has been read.
+    pub(crate) fn finish(self) -> Result<(), EmulatorError> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(EmulatorError::CorruptState)
+        }
+    }
+
+    pub(crate) fn bytes(&mut self, len: usize) -> Result<&'a [u8], EmulatorError> {
+        if len > self.bytes.len() {
+            return Err(EmulatorError::CorruptState);
+        }
+
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    pub(crate) fn array<const N: usize>(&mut self) -> Result<[u8; N], EmulatorError> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, EmulatorError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub(crate) fn bool(&mut self) -> Result<bool, EmulatorError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(EmulatorError::CorruptState),
+        }
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, EmulatorError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, EmulatorError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, EmulatorError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+}
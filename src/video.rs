@@ -1,15 +1,22 @@
-use sdl2::{pixels::Color, rect::Rect, render::Canvas, video::Window, Sdl};
+use crate::{
+    error::EmulatorError,
+    state::{Reader, Writer},
+};
 
-const DISPLAY_WIDTH: usize = 64;
-const DISPLAY_HEIGHT: usize = 32;
+/// Screen width in pixels.
+pub const DISPLAY_WIDTH: usize = 64;
+/// Screen height in pixels.
+pub const DISPLAY_HEIGHT: usize = 32;
 
-const SCALE_FACTOR: u32 = 20;
-const SCREEN_WIDTH: u32 = DISPLAY_WIDTH as u32 * SCALE_FACTOR;
-const SCREEN_HEIGHT: u32 = DISPLAY_HEIGHT as u32 * SCALE_FACTOR;
+/// Screen width in pixels in SUPER-CHIP hi-res mode.
+pub const HIRES_WIDTH: usize = 128;
+/// Screen height in pixels in SUPER-CHIP hi-res mode.
+pub const HIRES_HEIGHT: usize = 64;
 
-const COLOR_BG: Color = Color::RGB(0, 0, 0);
-const COLOR_FG: Color = Color::RGB(255, 255, 255);
+/// Number of XO-CHIP bitplanes.
+pub const PLANE_COUNT: usize = 2;
 
+/// Built-in 4x5 hexadecimal digit sprites, loaded at the start of memory.
 pub const FONT_SET: [u8; 80] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
     0x20, 0x60, 0x20, 0x20, 0x70, // 1
@@ -29,82 +36,441 @@ pub const FONT_SET: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
+/// SUPER-CHIP 8x10 hexadecimal digit sprites, loaded right after [`FONT_SET`].
+pub const BIG_FONT_SET: [u8; 160] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+    0x3C, 0x7E, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFE, 0xC3, 0xC3, 0xFE, 0xFE, 0xC3, 0xC3, 0xFE, 0xFC, // B
+    0x3C, 0x7E, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0x7E, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFC, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFC, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+/// The framebuffer.
+///
+/// The buffer is always big enough for hi-res mode; in lo-res mode only the top left
+/// [`DISPLAY_WIDTH`] x [`DISPLAY_HEIGHT`] pixels are used.
+///
+/// Each pixel holds one bit per bitplane, so its value is a colour index from 0 to 3. Only
+/// XO-CHIP programs ever select the second plane; everything else only draws colours 0 and 1.
 pub struct Display {
-    ram: [[bool; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
-    canvas: Canvas<Window>,
+    ram: [[u8; HIRES_WIDTH]; HIRES_HEIGHT],
+    hires: bool,
+    planes: u8,
     update: bool,
+
+    /// How pixels going off keep showing. A setting rather than state, left out of save
+    /// states.
+    pub persistence: Persistence,
+    /// Colour index each pixel was last lit in at the end of a frame, and how brightly it
+    /// still glows, from 255 down to 0.
+    glow: [[(u8, u8); HIRES_WIDTH]; HIRES_HEIGHT],
+    /// The framebuffer at the end of the last frame, and of the one before.
+    frames: [[[u8; HIRES_WIDTH]; HIRES_HEIGHT]; 2],
 }
 
-impl Display {
-    pub fn new(sdl: &Sdl) -> Self {
-        let mut canvas = sdl
-            .video()
-            .unwrap()
-            .window("chip8", SCREEN_WIDTH, SCREEN_HEIGHT)
-            .position_centered()
-            .opengl()
-            .build()
-            .unwrap()
-            .into_canvas()
-            .build()
-            .unwrap();
-
-        canvas.set_draw_color(COLOR_BG);
-        canvas.clear();
-        canvas.present();
+/// How pixels that go off keep showing for a while, hiding the flicker of sprites erased and
+/// drawn again with XOR.
+///
+/// Only what [`Display::shade`] reports changes; the framebuffer programs see is the same.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Persistence {
+    /// Pixels go off at once.
+    #[default]
+    Off,
+    /// Pixels fade out over this many frames, like the phosphor of a CRT.
+    Fade(u8),
+    /// Every frame is shown mixed half and half with the one before.
+    Blend,
+}
+
+impl Persistence {
+    /// Frames a pixel takes to fade out with `fade`.
+    pub const DEFAULT_FADE: u8 = 4;
+
+    /// Parse `off`, `blend`, `fade`, or a number of frames to fade out over.
+    pub fn from_name(name: &str) -> Option<Persistence> {
+        match name.to_ascii_lowercase().as_str() {
+            "off" | "0" => Some(Persistence::Off),
+            "blend" => Some(Persistence::Blend),
+            "fade" => Some(Persistence::Fade(Persistence::DEFAULT_FADE)),
+            frames => frames.parse().ok().map(Persistence::Fade),
+        }
+    }
+}
 
+/// What a pixel shows: colour index `pixel` mixed over colour index `behind`, `weight` out of
+/// 255 of the way from the second to the first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Shade {
+    pub pixel: u8,
+    pub behind: u8,
+    pub weight: u8,
+}
+
+impl Shade {
+    /// Colour index `pixel` alone.
+    pub fn solid(pixel: u8) -> Self {
+        Shade {
+            pixel,
+            behind: pixel,
+            weight: u8::MAX,
+        }
+    }
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display {
+    pub fn new() -> Self {
         Display {
-            ram: [[false; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
-            canvas: canvas,
+            ram: [[0; HIRES_WIDTH]; HIRES_HEIGHT],
+            hires: false,
+            planes: 1,
             update: false,
+            persistence: Persistence::Off,
+            glow: [[(0, 0); HIRES_WIDTH]; HIRES_HEIGHT],
+            frames: [[[0; HIRES_WIDTH]; HIRES_HEIGHT]; 2],
         }
     }
 
-    pub fn draw(&mut self) {
-        if !self.update {
-            return;
+    /// Width in pixels of the current resolution.
+    pub fn width(&self) -> usize {
+        if self.hires {
+            HIRES_WIDTH
+        } else {
+            DISPLAY_WIDTH
         }
+    }
 
-        for (y, row) in self.ram.iter().enumerate() {
-            let ry = (y as i32) * (SCALE_FACTOR as i32);
+    /// Height in pixels of the current resolution.
+    pub fn height(&self) -> usize {
+        if self.hires {
+            HIRES_HEIGHT
+        } else {
+            DISPLAY_HEIGHT
+        }
+    }
 
-            for (x, &col) in row.iter().enumerate() {
-                let rx = (x as i32) * (SCALE_FACTOR as i32);
+    pub fn hires(&self) -> bool {
+        self.hires
+    }
 
-                if col {
-                    self.canvas.set_draw_color(COLOR_FG)
-                } else {
-                    self.canvas.set_draw_color(COLOR_BG)
-                }
+    /// Switch between lo-res and hi-res mode, clearing every plane.
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.ram = [[0; HIRES_WIDTH]; HIRES_HEIGHT];
+        self.glow = [[(0, 0); HIRES_WIDTH]; HIRES_HEIGHT];
+        self.frames = [self.ram; 2];
+        self.update = true;
+    }
 
-                let _ = self
-                    .canvas
-                    .fill_rect(Rect::new(rx, ry, SCALE_FACTOR, SCALE_FACTOR));
+    /// Select the planes affected by drawing, clearing and scrolling, as a bitmask.
+    pub fn select_planes(&mut self, planes: u8) {
+        self.planes = planes & 0b11
+    }
+
+    /// The selected planes, each as a single-bit mask.
+    pub fn selected_planes(&self) -> impl Iterator<Item = u8> {
+        let planes = self.planes;
+        (0..PLANE_COUNT)
+            .map(|plane| 1 << plane)
+            .filter(move |mask| planes & mask != 0)
+    }
+
+    /// Rows of colour indices of the current resolution, top to bottom.
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        let width = self.width();
+        self.ram[..self.height()]
+            .iter()
+            .map(move |row| &row[..width])
+    }
+
+    /// What the pixel at `x`, `y` of the current resolution shows, with
+    /// [`Display::persistence`].
+    pub fn shade(&self, x: usize, y: usize) -> Shade {
+        let pixel = self.ram[y][x];
+
+        match self.persistence {
+            Persistence::Off => Shade::solid(pixel),
+            Persistence::Fade(_) => match self.glow[y][x] {
+                (glow, level) if pixel == 0 && level > 0 => Shade {
+                    pixel: glow,
+                    behind: 0,
+                    weight: level,
+                },
+                _ => Shade::solid(pixel),
+            },
+            Persistence::Blend => Shade {
+                pixel,
+                behind: self.frames[1][y][x],
+                weight: 128,
+            },
+        }
+    }
+
+    /// Advance [`Display::persistence`] by a frame, at the end of every frame.
+    pub(crate) fn end_frame(&mut self) {
+        match self.persistence {
+            Persistence::Off => {}
+            Persistence::Fade(frames) => {
+                let step = (u8::MAX as u16).div_ceil(frames.max(1) as u16) as u8;
+
+                for (glows, pixels) in self.glow.iter_mut().zip(&self.ram) {
+                    for (glow, &pixel) in glows.iter_mut().zip(pixels) {
+                        if pixel != 0 {
+                            *glow = (pixel, u8::MAX);
+                        } else if glow.1 > 0 {
+                            glow.1 = glow.1.saturating_sub(step);
+                            self.update = true;
+                        }
+                    }
+                }
+            }
+            Persistence::Blend => {
+                let behind = self.frames[0];
+                self.update |= behind != self.frames[1];
+                self.frames = [self.ram, behind];
             }
         }
+    }
 
-        self.canvas.present();
-        self.update = false
+    /// Whether the framebuffer changed since the last call.
+    pub fn consume_update(&mut self) -> bool {
+        let update = self.update;
+        self.update = false;
+        update
     }
 
-    pub fn set(&mut self, x: usize, y: usize, fill: bool) -> bool {
-        let cx = x % DISPLAY_WIDTH;
-        let cy = y % DISPLAY_HEIGHT;
+    pub(crate) fn save(&self, writer: &mut Writer) {
+        writer.bool(self.hires);
+        writer.u8(self.planes);
+
+        for row in &self.ram {
+            writer.bytes(row);
+        }
+    }
 
-        let collision = fill && self.ram[cy][cx];
-        self.ram[cy][cx] ^= fill;
+    pub(crate) fn load(reader: &mut Reader) -> Result<Self, EmulatorError> {
+        let mut display = Display::new();
+        display.hires = reader.bool()?;
+        display.planes = reader.u8()? & 0b11;
+
+        for row in display.ram.iter_mut() {
+            *row = reader.array()?;
+        }
+
+        display.update = true;
+        Ok(display)
+    }
+
+    /// XOR a pixel of `plane`, wrapping around the screen edges. Returns whether a lit pixel
+    /// was erased.
+    pub fn set(&mut self, plane: u8, x: usize, y: usize, fill: bool) -> bool {
+        let cx = x % self.width();
+        let cy = y % self.height();
+
+        if !fill {
+            return false;
+        }
+
+        let collision = self.ram[cy][cx] & plane != 0;
+        self.ram[cy][cx] ^= plane;
 
         self.update = true;
         collision
     }
 
+    /// Turn every pixel of the selected planes off.
     pub fn clear(&mut self) {
-        for x in 0..DISPLAY_WIDTH {
-            for y in 0..DISPLAY_HEIGHT {
-                self.ram[y][x] = false;
+        let keep = !self.planes;
+
+        for row in self.ram.iter_mut() {
+            for pixel in row.iter_mut() {
+                *pixel &= keep
             }
         }
 
         self.update = true;
     }
+
+    /// Move the selected planes down by `n` pixels, blanking the rows scrolled in at the top.
+    pub fn scroll_down(&mut self, n: usize) {
+        let height = self.height();
+
+        for y in (0..height).rev() {
+            let source = if y >= n { Some(self.ram[y - n]) } else { None };
+            self.shift_row(y, |x| source.map_or(0, |row| row[x]));
+        }
+
+        self.update = true;
+    }
+
+    /// Move the selected planes left by `n` pixels, blanking the columns scrolled in at the
+    /// right.
+    pub fn scroll_left(&mut self, n: usize) {
+        let width = self.width();
+
+        for y in 0..self.height() {
+            let row = self.ram[y];
+            self.shift_row(y, |x| if x + n < width { row[x + n] } else { 0 });
+        }
+
+        self.update = true;
+    }
+
+    /// Move the selected planes right by `n` pixels, blanking the columns scrolled in at the
+    /// left.
+    pub fn scroll_right(&mut self, n: usize) {
+        for y in 0..self.height() {
+            let row = self.ram[y];
+            self.shift_row(y, |x| if x >= n { row[x - n] } else { 0 });
+        }
+
+        self.update = true;
+    }
+
+    /// Replace the selected planes of row `y` with the ones of `source(x)` for every `x`.
+    fn shift_row<F: Fn(usize) -> u8>(&mut self, y: usize, source: F) {
+        let planes = self.planes;
+
+        for x in 0..self.width() {
+            let pixel = &mut self.ram[y][x];
+            *pixel = (*pixel & !planes) | (source(x) & planes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A display with `persistence` and its top left pixel lit for a frame.
+    fn lit(persistence: Persistence) -> Display {
+        let mut display = Display::new();
+        display.persistence = persistence;
+        display.set(1, 0, 0, true);
+        display.end_frame();
+        display
+    }
+
+    #[test]
+    fn off_shows_the_framebuffer() {
+        let mut display = lit(Persistence::Off);
+        assert_eq!(display.shade(0, 0), Shade::solid(1));
+
+        display.set(1, 0, 0, true);
+        display.end_frame();
+        assert_eq!(display.shade(0, 0), Shade::solid(0));
+    }
+
+    #[test]
+    fn fade_dims_over_its_frames() {
+        let mut display = lit(Persistence::Fade(4));
+        assert_eq!(display.shade(0, 0), Shade::solid(1));
+
+        display.set(1, 0, 0, true);
+        let mut levels = Vec::new();
+
+        for _ in 0..4 {
+            display.end_frame();
+            levels.push(display.shade(0, 0));
+        }
+
+        let glow = |weight| Shade {
+            pixel: 1,
+            behind: 0,
+            weight,
+        };
+        assert_eq!(levels[..3], [glow(191), glow(127), glow(63)]);
+        assert_eq!(levels[3], Shade::solid(0));
+        assert!(display.consume_update());
+
+        // A frame more with nothing lit or fading changes nothing.
+        display.end_frame();
+        assert!(!display.consume_update());
+    }
+
+    #[test]
+    fn fading_pixel_coming_back_on() {
+        let mut display = lit(Persistence::Fade(2));
+        display.set(1, 0, 0, true);
+        display.end_frame();
+        assert_eq!(display.shade(0, 0).weight, 127);
+
+        display.set(1, 0, 0, true);
+        assert_eq!(display.shade(0, 0), Shade::solid(1));
+
+        // Lit again for a frame, it fades from full brightness.
+        display.end_frame();
+        display.set(1, 0, 0, true);
+        display.end_frame();
+        assert_eq!(display.shade(0, 0).weight, 127);
+    }
+
+    #[test]
+    fn blend_mixes_with_the_frame_before() {
+        let mut display = lit(Persistence::Blend);
+        let blend = |pixel, behind| Shade {
+            pixel,
+            behind,
+            weight: 128,
+        };
+        assert_eq!(display.shade(0, 0), blend(1, 0));
+
+        display.end_frame();
+        assert_eq!(display.shade(0, 0), blend(1, 1));
+
+        display.set(1, 0, 0, true);
+        display.end_frame();
+        assert_eq!(display.shade(0, 0), blend(0, 1));
+
+        display.end_frame();
+        assert_eq!(display.shade(0, 0), blend(0, 0));
+    }
+
+    #[test]
+    fn changing_resolution_forgets_the_history() {
+        // Blended pixels keep their weight, so only what is mixed tells them dark.
+        let dark = |shade: Shade| (shade.pixel, shade.behind) == (0, 0);
+
+        for persistence in [Persistence::Fade(4), Persistence::Blend] {
+            let mut display = lit(persistence);
+            display.set(1, 0, 0, true);
+            display.end_frame();
+            assert!(!dark(display.shade(0, 0)));
+
+            display.set_hires(true);
+            assert!(dark(display.shade(0, 0)));
+
+            display.end_frame();
+            assert!(dark(display.shade(0, 0)));
+        }
+    }
+
+    #[test]
+    fn persistence_names() {
+        assert_eq!(Persistence::from_name("Blend"), Some(Persistence::Blend));
+        assert_eq!(Persistence::from_name("0"), Some(Persistence::Off));
+        assert_eq!(
+            Persistence::from_name("fade"),
+            Some(Persistence::Fade(Persistence::DEFAULT_FADE))
+        );
+        assert_eq!(Persistence::from_name("12"), Some(Persistence::Fade(12)));
+        assert_eq!(Persistence::from_name("300"), None);
+    }
 }